@@ -0,0 +1,139 @@
+// src/audit.rs
+
+use anyhow::Result;
+use regex::Regex;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use crate::llm::{LlmConfig, TokenUsage};
+
+const REDACTED: &str = "[REDACTED]";
+
+// 網址中的 key 查詢參數 (Gemini 以 ?key=... 傳送 API key)
+static URL_KEY: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(https?://[^\s"'<>?]*\?(?:[^\s"'<>#]*&)?key=)[^&\s"'<>#)\\]+"#).unwrap()
+});
+
+// 稽核紀錄的一行 (JSONL)
+#[derive(Debug, Serialize)]
+pub struct AuditRecord<'a> {
+    pub timestamp: u64,
+    pub chapter: &'a str,
+    pub pass: &'a str,
+    pub provider: &'a str,
+    pub model: &'a str,
+    pub format: String,
+    pub system_prompt: &'a str,
+    pub content: &'a str, // 送出的章節內文，供 --replay 重播
+    pub content_hash: String,
+    pub content_chars: usize,
+    pub response: Option<&'a str>,
+    pub cached: bool,
+    pub latency_ms: u128,
+    pub usage: Option<TokenUsage>,
+    pub error: Option<String>,
+}
+
+/// 將每次 LLM 呼叫附加寫入 JSONL 檔
+pub struct AuditLog {
+    path: PathBuf,
+    secrets: Vec<String>,
+}
+
+impl AuditLog {
+    pub fn new(path: &Path, llm: &LlmConfig) -> Result<Self> {
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs_err::create_dir_all(parent)?;
+        }
+        let secrets = [
+            llm.gemini.as_ref().map(|c| c.api_key.clone()),
+            llm.openai.as_ref().map(|c| c.api_key.clone()),
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|key| !key.is_empty())
+        .collect();
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            secrets,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write(&self, record: &AuditRecord<'_>) -> Result<()> {
+        let line = self.redact(&serde_json::to_string(record)?);
+        let mut file = fs_err::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", line)?;
+        Ok(())
+    }
+
+    fn redact(&self, text: &str) -> String {
+        let text = self.secrets.iter().fold(text.to_string(), |acc, secret| {
+            acc.replace(secret, REDACTED)
+        });
+        redact_query_key(&text)
+    }
+}
+
+// 錯誤訊息中的 URL 可能帶有 ?key=...，只遮蔽網址中的 key 參數，prompt 與回應中的 key= 保持原樣
+fn redact_query_key(text: &str) -> String {
+    URL_KEY
+        .replace_all(text, format!("${{1}}{}", REDACTED))
+        .into_owned()
+}
+
+pub fn content_hash(content: &str) -> String {
+    Sha256::digest(content.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AuditLog, content_hash, redact_query_key};
+    use std::path::PathBuf;
+
+    #[test]
+    fn query_keys_are_redacted() {
+        let text = "error sending request for url (https://x/y:generateContent?key=AIzaSECRET)";
+        assert_eq!(
+            redact_query_key(text),
+            "error sending request for url (https://x/y:generateContent?key=[REDACTED])"
+        );
+        assert_eq!(redact_query_key("no secrets"), "no secrets");
+        assert_eq!(
+            redact_query_key("url (http://h/v1?alt=sse&key=abc&x=1) monkey=banana"),
+            "url (http://h/v1?alt=sse&key=[REDACTED]&x=1) monkey=banana"
+        );
+    }
+
+    #[test]
+    fn configured_api_keys_are_redacted() {
+        let log = AuditLog {
+            path: PathBuf::from("unused.jsonl"),
+            secrets: vec!["sk-secret".to_string()],
+        };
+        assert_eq!(log.redact("Bearer sk-secret"), "Bearer [REDACTED]");
+    }
+
+    #[test]
+    fn content_hash_is_sha256_hex() {
+        assert_eq!(
+            content_hash("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
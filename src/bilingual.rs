@@ -0,0 +1,220 @@
+// src/bilingual.rs
+
+use serde::Deserialize;
+
+/// 原文與譯文對照輸出的格式
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BilingualFormat {
+    Txt,
+    #[serde(alias = "md")]
+    Markdown,
+    Html,
+}
+
+/// 對齊後的一組段落 (任一邊可能是多段合併或空白)
+#[derive(Debug, PartialEq)]
+pub struct AlignedPair {
+    pub source: String,
+    pub translation: String,
+}
+
+// 合併或漏譯的段落額外加上的成本
+const MERGE_PENALTY: f64 = 0.3;
+const SKIP_PENALTY: f64 = 1.0;
+
+// 允許的對齊方式：(原文段落數, 譯文段落數)
+const BEADS: [(usize, usize); 5] = [(1, 1), (1, 2), (2, 1), (1, 0), (0, 1)];
+
+fn char_len(paragraphs: &[&str]) -> f64 {
+    paragraphs.iter().map(|p| p.chars().count()).sum::<usize>() as f64
+}
+
+/// 段落對齊：數量相同時逐段配對，否則依長度比例以動態規劃找出最佳的合併方式
+pub fn align(source: &[&str], translation: &[&str]) -> Vec<AlignedPair> {
+    if source.len() == translation.len() {
+        return source
+            .iter()
+            .zip(translation)
+            .map(|(s, t)| AlignedPair {
+                source: s.to_string(),
+                translation: t.to_string(),
+            })
+            .collect();
+    }
+
+    // 譯文與原文的整體長度比例 (不同語言的字數差異很大)
+    let ratio = char_len(translation) / char_len(source).max(1.0);
+    let bead_cost = |s: &[&str], t: &[&str]| -> f64 {
+        match (s.len(), t.len()) {
+            (0, _) | (_, 0) => SKIP_PENALTY,
+            (ls, lt) => {
+                let expected = char_len(s) * ratio;
+                let actual = char_len(t);
+                let diff = (actual - expected).abs() / (actual + expected).max(1.0);
+                if ls == 1 && lt == 1 {
+                    diff
+                } else {
+                    diff + MERGE_PENALTY
+                }
+            }
+        }
+    };
+
+    let (n, m) = (source.len(), translation.len());
+    let mut cost = vec![vec![f64::INFINITY; m + 1]; n + 1];
+    let mut back = vec![vec![(0, 0); m + 1]; n + 1];
+    cost[0][0] = 0.0;
+    for i in 0..=n {
+        for j in 0..=m {
+            if cost[i][j].is_infinite() {
+                continue;
+            }
+            for (di, dj) in BEADS {
+                let (ni, nj) = (i + di, j + dj);
+                if ni > n || nj > m {
+                    continue;
+                }
+                let total = cost[i][j] + bead_cost(&source[i..ni], &translation[j..nj]);
+                if total < cost[ni][nj] {
+                    cost[ni][nj] = total;
+                    back[ni][nj] = (di, dj);
+                }
+            }
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (n, m);
+    while i > 0 || j > 0 {
+        let (di, dj) = back[i][j];
+        pairs.push(AlignedPair {
+            source: source[i - di..i].join("\n"),
+            translation: translation[j - dj..j].join("\n"),
+        });
+        i -= di;
+        j -= dj;
+    }
+    pairs.reverse();
+    pairs
+}
+
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl BilingualFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            BilingualFormat::Txt => "txt",
+            BilingualFormat::Markdown => "md",
+            BilingualFormat::Html => "html",
+        }
+    }
+
+    /// 產生對照版全文；source_label / target_label 為欄位標題
+    pub fn render(
+        self,
+        title: &str,
+        source_label: &str,
+        target_label: &str,
+        pairs: &[AlignedPair],
+    ) -> String {
+        match self {
+            BilingualFormat::Txt => pairs
+                .iter()
+                .map(|p| format!("{}\n{}\n", p.source, p.translation))
+                .collect::<Vec<_>>()
+                .join("\n--------\n\n"),
+            BilingualFormat::Markdown => {
+                let cell = |text: &str| text.replace('|', "\\|").replace('\n', "<br>");
+                let mut out = format!(
+                    "# {}\n\n| {} | {} |\n| --- | --- |\n",
+                    title,
+                    cell(source_label),
+                    cell(target_label)
+                );
+                for p in pairs {
+                    out.push_str(&format!(
+                        "| {} | {} |\n",
+                        cell(&p.source),
+                        cell(&p.translation)
+                    ));
+                }
+                out
+            }
+            BilingualFormat::Html => {
+                let cell = |text: &str| escape_html(text).replace('\n', "<br>");
+                let rows: String = pairs
+                    .iter()
+                    .map(|p| {
+                        format!(
+                            "<tr><td>{}</td><td>{}</td></tr>\n",
+                            cell(&p.source),
+                            cell(&p.translation)
+                        )
+                    })
+                    .collect();
+                format!(
+                    "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+                     <style>table{{border-collapse:collapse;width:100%}}td,th{{border:1px solid #ccc;padding:.4em;vertical-align:top;width:50%}}</style>\n\
+                     </head>\n<body>\n<h1>{title}</h1>\n<table>\n<tr><th>{}</th><th>{}</th></tr>\n{}</table>\n</body>\n</html>\n",
+                    escape_html(source_label),
+                    escape_html(target_label),
+                    rows,
+                    title = escape_html(title)
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AlignedPair, BilingualFormat, align};
+
+    #[test]
+    fn equal_paragraph_counts_pair_one_to_one() {
+        let pairs = align(&["一", "二"], &["one", "two"]);
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(pairs[1].source, "二");
+        assert_eq!(pairs[1].translation, "two");
+    }
+
+    #[test]
+    fn split_translation_paragraph_is_merged() {
+        let source = [
+            "短い文。",
+            "とても長い段落がここにあって、翻訳では二つに分けられた。",
+            "終わり。",
+        ];
+        let translation = [
+            "短句。",
+            "這裡有一段很長的段落，",
+            "翻譯時被拆成兩段。",
+            "結束。",
+        ];
+        let pairs = align(&source, &translation);
+        assert_eq!(pairs.len(), 3);
+        assert_eq!(
+            pairs[1].translation,
+            "這裡有一段很長的段落，\n翻譯時被拆成兩段。"
+        );
+        assert_eq!(pairs[2].source, "終わり。");
+    }
+
+    #[test]
+    fn html_and_markdown_escape_cells() {
+        let pairs = [AlignedPair {
+            source: "a|b".to_string(),
+            translation: "<x>".to_string(),
+        }];
+        let md = BilingualFormat::Markdown.render("t", "原文", "譯文", &pairs);
+        assert!(md.contains("| a\\|b | <x> |"));
+        let html = BilingualFormat::Html.render("t", "原文", "譯文", &pairs);
+        assert!(html.contains("<td>&lt;x&gt;</td>"));
+    }
+}
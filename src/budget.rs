@@ -0,0 +1,223 @@
+// src/budget.rs
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::llm::TokenUsage;
+use crate::usage::{RunManifest, unix_now};
+
+// runtime.budget 區塊，所有上限皆為選填
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct BudgetConfig {
+    pub max_tokens_per_run: Option<u64>,
+    pub max_cost_per_run: Option<f64>,
+    pub max_requests_per_run: Option<u64>,
+    pub max_tokens_per_day: Option<u64>,
+    pub max_cost_per_day: Option<f64>,
+    pub max_requests_per_day: Option<u64>,
+}
+
+impl BudgetConfig {
+    pub fn has_cost_limit(&self) -> bool {
+        self.max_cost_per_run.is_some() || self.max_cost_per_day.is_some()
+    }
+
+    /// 檢查是否已達上限，回傳第一個觸發的原因
+    pub fn exceeded(&self, run: &RunManifest, today: &DailyUsage) -> Option<String> {
+        let run_cost = run.estimated_cost.unwrap_or(0.0);
+        let checks = [
+            check_count("本次執行 token", run.usage.total(), self.max_tokens_per_run),
+            check_count("本次執行請求數", run.requests, self.max_requests_per_run),
+            check_cost("本次執行費用", run_cost, self.max_cost_per_run),
+            check_count("今日 token", today.usage.total(), self.max_tokens_per_day),
+            check_count("今日請求數", today.requests, self.max_requests_per_day),
+            check_cost("今日費用", today.estimated_cost, self.max_cost_per_day),
+        ];
+        checks.into_iter().flatten().next()
+    }
+}
+
+fn check_count(label: &str, value: u64, limit: Option<u64>) -> Option<String> {
+    let limit = limit?;
+    (value >= limit).then(|| format!("{}已達 {} (上限 {})", label, value, limit))
+}
+
+fn check_cost(label: &str, value: f64, limit: Option<f64>) -> Option<String> {
+    let limit = limit?;
+    (value >= limit).then(|| format!("{}已達 ${:.4} (上限 ${:.4})", label, value, limit))
+}
+
+// 單日累計 (跨多次執行)
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DailyUsage {
+    pub requests: u64,
+    pub usage: TokenUsage,
+    pub estimated_cost: f64,
+}
+
+// 每日用量帳本，存放於 glossary_folder/runs/daily_usage.json
+pub struct DailyLedger {
+    path: PathBuf,
+    days: BTreeMap<String, DailyUsage>,
+    synced: DailyUsage, // 本次執行已寫入帳本的部分
+}
+
+impl DailyLedger {
+    /// 帳本不存在時從空白開始；存在但無法讀取或解析時回傳錯誤，避免重設每日上限並覆寫原檔
+    pub fn load(glossary_folder: &Path) -> Result<Self> {
+        let path = glossary_folder.join("runs").join("daily_usage.json");
+        let days = if path.exists() {
+            let content = fs_err::read_to_string(&path)?;
+            serde_json::from_str(&content).with_context(|| {
+                format!(
+                    "每日用量帳本 {} 無法解析，請修正或移除後再執行",
+                    path.display()
+                )
+            })?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self {
+            path,
+            days,
+            synced: DailyUsage::default(),
+        })
+    }
+
+    pub fn today(&self) -> DailyUsage {
+        self.days.get(&today_key()).cloned().unwrap_or_default()
+    }
+
+    /// 將本次執行尚未入帳的用量加到今日
+    pub fn sync(&mut self, run: &RunManifest) {
+        let run_cost = run.estimated_cost.unwrap_or(0.0);
+        let day = self.days.entry(today_key()).or_default();
+        day.requests += run.requests - self.synced.requests;
+        day.usage.prompt_tokens += run.usage.prompt_tokens - self.synced.usage.prompt_tokens;
+        day.usage.completion_tokens +=
+            run.usage.completion_tokens - self.synced.usage.completion_tokens;
+        day.estimated_cost += run_cost - self.synced.estimated_cost;
+
+        self.synced = DailyUsage {
+            requests: run.requests,
+            usage: run.usage,
+            estimated_cost: run_cost,
+        };
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs_err::create_dir_all(parent)?;
+        }
+        fs_err::write(&self.path, serde_json::to_string_pretty(&self.days)?)?;
+        Ok(())
+    }
+}
+
+// 以 UTC 日期作為帳本 key (YYYY-MM-DD)
+fn today_key() -> String {
+    date_key(unix_now() / 86_400)
+}
+
+// days since epoch -> 西元日期 (Howard Hinnant 的 civil_from_days)
+fn date_key(days: u64) -> String {
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BudgetConfig, DailyLedger, DailyUsage, date_key};
+    use crate::llm::TokenUsage;
+    use crate::usage::UsageTracker;
+    use std::path::Path;
+
+    fn usage(prompt: u64, completion: u64) -> TokenUsage {
+        TokenUsage {
+            prompt_tokens: prompt,
+            completion_tokens: completion,
+        }
+    }
+
+    #[test]
+    fn date_key_converts_epoch_days() {
+        assert_eq!(date_key(0), "1970-01-01");
+        assert_eq!(date_key(19_783), "2024-03-01");
+    }
+
+    #[test]
+    fn budget_reports_run_token_limit() {
+        let budget = BudgetConfig {
+            max_tokens_per_run: Some(1_000),
+            ..Default::default()
+        };
+        let mut tracker = UsageTracker::new("openai", "gpt-4o", None);
+        tracker.record("001", "analysis", usage(400, 100));
+        assert!(
+            budget
+                .exceeded(tracker.manifest(), &DailyUsage::default())
+                .is_none()
+        );
+
+        tracker.record("001", "translation", usage(400, 100));
+        assert!(
+            budget
+                .exceeded(tracker.manifest(), &DailyUsage::default())
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn budget_reports_daily_request_limit() {
+        let budget = BudgetConfig {
+            max_requests_per_day: Some(10),
+            ..Default::default()
+        };
+        let tracker = UsageTracker::new("openai", "gpt-4o", None);
+        let today = DailyUsage {
+            requests: 10,
+            ..Default::default()
+        };
+        assert!(budget.exceeded(tracker.manifest(), &today).is_some());
+    }
+
+    #[test]
+    fn ledger_sync_only_adds_new_usage() {
+        let mut ledger = DailyLedger::load(Path::new("does-not-exist")).unwrap();
+        let mut tracker = UsageTracker::new("openai", "gpt-4o", None);
+
+        tracker.record("001", "analysis", usage(100, 10));
+        ledger.sync(tracker.manifest());
+        tracker.record("001", "translation", usage(200, 20));
+        ledger.sync(tracker.manifest());
+
+        let today = ledger.today();
+        assert_eq!(today.requests, 2);
+        assert_eq!(today.usage, usage(300, 30));
+    }
+
+    #[test]
+    fn corrupt_ledger_is_an_error_and_left_untouched() {
+        let folder = std::env::temp_dir().join(format!(
+            "ai-novel-translation-ledger-{}",
+            std::process::id()
+        ));
+        let path = folder.join("runs").join("daily_usage.json");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "{ broken").unwrap();
+
+        assert!(DailyLedger::load(&folder).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{ broken");
+        let _ = std::fs::remove_dir_all(&folder);
+    }
+}
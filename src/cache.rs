@@ -0,0 +1,373 @@
+// src/cache.rs
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::FileTimes;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::llm::{LlmClient, LlmResponse, ResponseFormat, TokenUsage};
+
+fn default_cache_folder() -> PathBuf {
+    PathBuf::from("./cache")
+}
+
+fn default_max_size_mb() -> u64 {
+    200
+}
+
+// cache 區塊
+#[derive(Debug, Deserialize, Clone)]
+pub struct CacheConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_cache_folder")]
+    pub folder: PathBuf,
+    #[serde(default = "default_max_size_mb")]
+    pub max_size_mb: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            folder: default_cache_folder(),
+            max_size_mb: default_max_size_mb(),
+        }
+    }
+}
+
+// 快取檔案內容
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    text: String,
+    usage: TokenUsage, // 原始呼叫的用量，僅供參考
+}
+
+/// 包在任一 LlmClient 外層的磁碟快取
+pub struct CachedClient {
+    inner: Box<dyn LlmClient>,
+    folder: PathBuf,
+    max_size_bytes: u64,
+    namespace: String, // provider、模型、服務位址與生成參數 (LlmConfig::request_signature)
+    refresh: bool,     // true: 不讀取舊快取，但仍寫入新結果
+    size: Mutex<Option<u64>>, // 快取資料夾目前的總大小；第一次寫入時掃描一次，之後隨寫入累加
+}
+
+impl CachedClient {
+    pub fn new(
+        inner: Box<dyn LlmClient>,
+        config: &CacheConfig,
+        request_signature: &str,
+        refresh: bool,
+    ) -> Self {
+        Self {
+            inner,
+            folder: config.folder.clone(),
+            max_size_bytes: config.max_size_mb * 1024 * 1024,
+            namespace: request_signature.to_string(),
+            refresh,
+            size: Mutex::new(None),
+        }
+    }
+
+    fn entry_path(
+        &self,
+        system_prompt: &str,
+        user_content: &str,
+        format: &ResponseFormat,
+    ) -> PathBuf {
+        let key = cache_key(
+            &self.namespace,
+            system_prompt,
+            user_content,
+            &format.describe(),
+        );
+        self.folder.join(&key[..2]).join(format!("{}.json", key))
+    }
+
+    fn read(&self, path: &Path) -> Option<CacheEntry> {
+        let file = fs_err::File::open(path).ok()?;
+        let entry = serde_json::from_reader(&file).ok()?;
+        // 更新修改時間，讓容量清理以最近使用順序淘汰
+        let _ = file
+            .file()
+            .set_times(FileTimes::new().set_modified(SystemTime::now()));
+        Some(entry)
+    }
+
+    fn write(&self, path: &Path, entry: &CacheEntry) -> Result<()> {
+        let mut size = self.size.lock().unwrap_or_else(|e| e.into_inner());
+        let total = match *size {
+            Some(total) => total,
+            None => folder_size(&self.folder),
+        };
+        if let Some(parent) = path.parent() {
+            fs_err::create_dir_all(parent)?;
+        }
+        // refresh 模式會覆寫舊檔，先扣掉舊檔大小
+        let replaced = fs_err::metadata(path).map(|meta| meta.len()).unwrap_or(0);
+        let content = serde_json::to_string(entry)?;
+        fs_err::write(path, &content)?;
+        let total = total.saturating_sub(replaced) + content.len() as u64;
+        // 清理失敗時下次寫入重新掃描
+        *size = None;
+        let total = if total > self.max_size_bytes {
+            enforce_size_limit(&self.folder, self.max_size_bytes)?
+        } else {
+            total
+        };
+        *size = Some(total);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl LlmClient for CachedClient {
+    async fn generate(
+        &self,
+        system_prompt: &str,
+        user_content: &str,
+        format: &ResponseFormat,
+    ) -> Result<LlmResponse> {
+        let path = self.entry_path(system_prompt, user_content, format);
+
+        if !self.refresh
+            && let Some(entry) = self.read(&path)
+        {
+            return Ok(LlmResponse {
+                text: entry.text,
+                usage: TokenUsage::default(),
+                cached: true,
+            });
+        }
+
+        let resp = self
+            .inner
+            .generate(system_prompt, user_content, format)
+            .await?;
+        let entry = CacheEntry {
+            text: resp.text.clone(),
+            usage: resp.usage,
+        };
+        // 快取寫入失敗不影響翻譯流程
+        if let Err(e) = self.write(&path, &entry) {
+            eprintln!("[警告] 寫入回應快取失敗: {:?}", e);
+        }
+        Ok(resp)
+    }
+}
+
+// namespace 為實際送出的請求參數，加上輸出格式、prompt 與內文
+fn cache_key(namespace: &str, system_prompt: &str, user_content: &str, format: &str) -> String {
+    let mut hasher = Sha256::new();
+    for part in [namespace, format, system_prompt, user_content] {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part.as_bytes());
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+// 快取資料夾中的檔案 (修改時間, 大小, 路徑)
+fn cache_files(folder: &Path) -> Vec<(SystemTime, u64, PathBuf)> {
+    walkdir::WalkDir::new(folder)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| {
+            let meta = e.metadata().ok()?;
+            Some((meta.modified().ok()?, meta.len(), e.into_path()))
+        })
+        .collect()
+}
+
+fn folder_size(folder: &Path) -> u64 {
+    cache_files(folder).iter().map(|(_, len, _)| len).sum()
+}
+
+// 超過容量上限時，從最久未使用的檔案開始刪除，回傳清理後的總大小
+fn enforce_size_limit(folder: &Path, max_size_bytes: u64) -> Result<u64> {
+    let mut entries = cache_files(folder);
+    let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+    if total <= max_size_bytes {
+        return Ok(total);
+    }
+
+    entries.sort();
+    for (_, len, path) in entries {
+        if total <= max_size_bytes {
+            break;
+        }
+        fs_err::remove_file(&path)?;
+        total -= len;
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CacheConfig, CachedClient, cache_key, enforce_size_limit, folder_size};
+    use crate::llm::{LlmClient, LlmResponse, ResponseFormat, TokenUsage};
+    use anyhow::Result;
+    use async_trait::async_trait;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct CountingClient {
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl LlmClient for CountingClient {
+        async fn generate(
+            &self,
+            _: &str,
+            user_content: &str,
+            _: &ResponseFormat,
+        ) -> Result<LlmResponse> {
+            let n = self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(LlmResponse {
+                text: format!("{}#{}", user_content, n),
+                usage: TokenUsage {
+                    prompt_tokens: 10,
+                    completion_tokens: 5,
+                },
+                cached: false,
+            })
+        }
+    }
+
+    fn temp_folder(name: &str) -> PathBuf {
+        let folder = std::env::temp_dir().join(format!(
+            "ai-novel-translation-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&folder);
+        folder
+    }
+
+    fn cached_client(folder: &Path, refresh: bool) -> (CachedClient, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let config = CacheConfig {
+            enabled: true,
+            folder: folder.to_path_buf(),
+            ..Default::default()
+        };
+        let inner = Box::new(CountingClient {
+            calls: calls.clone(),
+        });
+        (
+            CachedClient::new(inner, &config, "mock\nmodel", refresh),
+            calls,
+        )
+    }
+
+    #[test]
+    fn cache_key_depends_on_every_input() {
+        let base = cache_key("p\nm", "sys", "body", "json");
+        assert_eq!(base, cache_key("p\nm", "sys", "body", "json"));
+        assert_ne!(base, cache_key("p\nm2", "sys", "body", "json"));
+        assert_ne!(base, cache_key("p\nm", "sys2", "body", "json"));
+        assert_ne!(base, cache_key("p\nm", "sys", "body2", "json"));
+        assert_ne!(base, cache_key("p\nm", "sys", "body", "text"));
+    }
+
+    #[tokio::test]
+    async fn second_call_is_served_from_cache() {
+        let folder = temp_folder("cache-hit");
+        let (client, calls) = cached_client(&folder, false);
+
+        let first = client
+            .generate("sys", "body", &ResponseFormat::Text)
+            .await
+            .unwrap();
+        let second = client
+            .generate("sys", "body", &ResponseFormat::Text)
+            .await
+            .unwrap();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(first.text, second.text);
+        assert!(second.cached);
+        assert_eq!(second.usage, TokenUsage::default());
+        let _ = std::fs::remove_dir_all(&folder);
+    }
+
+    #[tokio::test]
+    async fn refresh_mode_bypasses_existing_entries() {
+        let folder = temp_folder("cache-refresh");
+        let (client, _) = cached_client(&folder, false);
+        client
+            .generate("sys", "body", &ResponseFormat::Text)
+            .await
+            .unwrap();
+
+        let (refreshing, calls) = cached_client(&folder, true);
+        let resp = refreshing
+            .generate("sys", "body", &ResponseFormat::Text)
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(!resp.cached);
+        let _ = std::fs::remove_dir_all(&folder);
+    }
+
+    #[test]
+    fn size_limit_removes_files_until_under_cap() {
+        let folder = temp_folder("cache-limit");
+        std::fs::create_dir_all(&folder).unwrap();
+        for i in 0..4 {
+            std::fs::write(folder.join(format!("{}.json", i)), "x".repeat(100)).unwrap();
+        }
+
+        assert_eq!(enforce_size_limit(&folder, 250).unwrap(), 200);
+        let remaining = std::fs::read_dir(&folder).unwrap().count();
+        assert_eq!(remaining, 2);
+        let _ = std::fs::remove_dir_all(&folder);
+    }
+
+    #[tokio::test]
+    async fn writes_keep_a_running_total_and_clean_up_past_the_cap() {
+        let folder = temp_folder("cache-running-total");
+        let old = folder.join("00").join("old.json");
+        std::fs::create_dir_all(old.parent().unwrap()).unwrap();
+        std::fs::write(&old, "x".repeat(100)).unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&old)
+            .unwrap()
+            .set_modified(std::time::UNIX_EPOCH)
+            .unwrap();
+
+        let (mut client, _) = cached_client(&folder, false);
+        client.max_size_bytes = 250;
+        for body in ["a", "b"] {
+            client
+                .generate("sys", body, &ResponseFormat::Text)
+                .await
+                .unwrap();
+        }
+        // 第一次寫入時掃描資料夾，之後只累加
+        assert_eq!(client.size.lock().unwrap().unwrap(), folder_size(&folder));
+        assert!(old.exists());
+
+        client
+            .generate("sys", "c", &ResponseFormat::Text)
+            .await
+            .unwrap();
+        assert!(!old.exists());
+        let size = client.size.lock().unwrap().unwrap();
+        assert_eq!(size, folder_size(&folder));
+        assert!(size <= 250);
+        let _ = std::fs::remove_dir_all(&folder);
+    }
+}
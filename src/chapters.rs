@@ -0,0 +1,240 @@
+// src/chapters.rs
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use std::cmp::Ordering;
+use std::path::{Component, Path, PathBuf};
+
+// 全形數字視為一般數字
+fn digit_value(c: char) -> Option<u32> {
+    match c {
+        '０'..='９' => Some(c as u32 - '０' as u32),
+        _ => c.to_digit(10),
+    }
+}
+
+// 將字串切成連續數字與非數字片段
+fn chunks(text: &str) -> Vec<(bool, String)> {
+    let mut chunks: Vec<(bool, String)> = Vec::new();
+    for c in text.chars() {
+        let is_digit = digit_value(c).is_some();
+        let c = digit_value(c).map_or(c, |d| char::from_digit(d, 10).unwrap());
+        match chunks.last_mut() {
+            Some((digit, chunk)) if *digit == is_digit => chunk.push(c),
+            _ => chunks.push((is_digit, c.to_string())),
+        }
+    }
+    chunks
+}
+
+/// 自然排序：數字片段依數值比較，例如 2 < 10、第2話 < 第10話
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (a_chunks, b_chunks) = (chunks(a), chunks(b));
+    // 大小寫或前導零不同時，只在其餘部分都相同時才用來決定順序
+    let mut tiebreak = Ordering::Equal;
+    for ((a_digit, a), (b_digit, b)) in a_chunks.iter().zip(&b_chunks) {
+        let ordering = if *a_digit && *b_digit {
+            let (a_trim, b_trim) = (a.trim_start_matches('0'), b.trim_start_matches('0'));
+            tiebreak = tiebreak.then_with(|| a.len().cmp(&b.len()));
+            a_trim
+                .len()
+                .cmp(&b_trim.len())
+                .then_with(|| a_trim.cmp(b_trim))
+        } else {
+            tiebreak = tiebreak.then_with(|| a.cmp(b));
+            a.to_lowercase().cmp(&b.to_lowercase())
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    a_chunks.len().cmp(&b_chunks.len()).then(tiebreak)
+}
+
+/// 逐層比較路徑，讓 vol2/ 排在 vol10/ 之前
+pub fn compare_paths(a: &Path, b: &Path) -> Ordering {
+    let a_parts: Vec<_> = a
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect();
+    let b_parts: Vec<_> = b
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect();
+    for (a, b) in a_parts.iter().zip(&b_parts) {
+        let ordering = natural_cmp(a, b);
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    a_parts.len().cmp(&b_parts.len())
+}
+
+/// 章節 ID：相對於 input_folder 的路徑去掉副檔名，子資料夾以 __ 連接 (vol1/001.txt -> vol1__001)
+/// 字典、用量與劇情記憶都以 ID 區分章節，不同卷中同名的檔案不會互相覆蓋
+pub fn chapter_id(input_folder: &Path, file: &Path) -> String {
+    let relative = file.strip_prefix(input_folder).unwrap_or(file);
+    let mut parts: Vec<String> = relative
+        .parent()
+        .map(|dir| {
+            dir.components()
+                .filter_map(|c| match c {
+                    Component::Normal(part) => Some(part.to_string_lossy().to_string()),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    parts.push(
+        file.file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string(),
+    );
+    parts.join("__")
+}
+
+pub fn sort_naturally(files: &mut [PathBuf]) {
+    files.sort_by(|a, b| compare_paths(a, b));
+}
+
+// chapters.toml 格式
+#[derive(Debug, Deserialize)]
+struct OrderManifest {
+    chapters: Vec<PathBuf>,
+}
+
+/// 讀取章節清單：.toml 使用 `chapters = [...]`，其他副檔名為一行一個路徑 (# 開頭為註解)
+fn read_order_file(path: &Path) -> Result<Vec<PathBuf>> {
+    let content = fs_err::read_to_string(path)?;
+    let is_toml = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"));
+    if is_toml {
+        let manifest: OrderManifest =
+            toml::from_str(&content).context(format!("無法解析章節清單 {}", path.display()))?;
+        return Ok(manifest.chapters);
+    }
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// 依章節清單決定處理順序；清單中的路徑相對於 input_folder
+/// 回傳 (章節, 不在清單中的檔案)
+pub fn apply_order_file(
+    input_folder: &Path,
+    order_file: &Path,
+    discovered: &[PathBuf],
+) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let mut chapters: Vec<PathBuf> = Vec::new();
+    for entry in read_order_file(order_file)? {
+        let path = input_folder.join(&entry);
+        if !path.is_file() {
+            bail!("章節清單中的檔案不存在: {}", path.display());
+        }
+        if chapters.contains(&path) {
+            bail!("章節清單中有重複的檔案: {}", entry.display());
+        }
+        chapters.push(path);
+    }
+    if chapters.is_empty() {
+        bail!("章節清單是空的: {}", order_file.display());
+    }
+
+    let mut unlisted: Vec<PathBuf> = discovered
+        .iter()
+        .filter(|file| !chapters.contains(file))
+        .cloned()
+        .collect();
+    sort_naturally(&mut unlisted);
+    Ok((chapters, unlisted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_order_file, chapter_id, natural_cmp, sort_naturally};
+    use std::cmp::Ordering;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn numbers_compare_by_value() {
+        assert_eq!(natural_cmp("2.txt", "10.txt"), Ordering::Less);
+        assert_eq!(natural_cmp("第９話", "第10話"), Ordering::Less);
+        assert_eq!(natural_cmp("002.txt", "2.txt"), Ordering::Greater);
+        assert_eq!(natural_cmp("Chapter 3", "chapter 12"), Ordering::Less);
+        assert_eq!(natural_cmp("chapter 3", "Chapter 12"), Ordering::Less);
+    }
+
+    #[test]
+    fn volume_directories_sort_before_their_siblings() {
+        let mut files: Vec<PathBuf> = [
+            "in/vol10/1.txt",
+            "in/vol2/10.txt",
+            "in/vol2/2.txt",
+            "in/vol1/1.txt",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+        sort_naturally(&mut files);
+        assert_eq!(
+            files,
+            [
+                "in/vol1/1.txt",
+                "in/vol2/2.txt",
+                "in/vol2/10.txt",
+                "in/vol10/1.txt"
+            ]
+            .iter()
+            .map(PathBuf::from)
+            .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn same_file_names_in_different_volumes_get_different_ids() {
+        let input = Path::new("./input");
+        assert_eq!(chapter_id(input, Path::new("./input/001.txt")), "001");
+        assert_eq!(
+            chapter_id(input, Path::new("./input/vol1/001.txt")),
+            "vol1__001"
+        );
+        assert_eq!(
+            chapter_id(input, Path::new("./input/vol2/001.md")),
+            "vol2__001"
+        );
+    }
+
+    #[test]
+    fn order_file_defines_chapters_and_reports_unlisted_files() {
+        let dir =
+            std::env::temp_dir().join(format!("ai-novel-translation-order-{}", std::process::id()));
+        let input = dir.join("input");
+        std::fs::create_dir_all(input.join("vol1")).unwrap();
+        for name in ["vol1/b.txt", "vol1/a.txt", "extra.txt"] {
+            std::fs::write(input.join(name), "x").unwrap();
+        }
+        let discovered: Vec<PathBuf> = ["vol1/b.txt", "vol1/a.txt", "extra.txt"]
+            .iter()
+            .map(|name| input.join(name))
+            .collect();
+
+        let toml_order = dir.join("chapters.toml");
+        std::fs::write(&toml_order, "chapters = [\"vol1/b.txt\", \"vol1/a.txt\"]").unwrap();
+        let (chapters, unlisted) = apply_order_file(&input, &toml_order, &discovered).unwrap();
+        assert_eq!(
+            chapters,
+            vec![input.join("vol1/b.txt"), input.join("vol1/a.txt")]
+        );
+        assert_eq!(unlisted, vec![input.join("extra.txt")]);
+
+        let text_order = dir.join("chapters.txt");
+        std::fs::write(&text_order, "# 序章\nextra.txt\n\nvol1/missing.txt\n").unwrap();
+        assert!(apply_order_file(&input, &text_order, &discovered).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,234 @@
+// src/characters.rs
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct Relationship {
+    pub character: String,
+    pub relation: String, // 例如 "妹妹"、"師父"
+}
+
+// 角色資料 (Pass 1 回應與字典檔共用)
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct Character {
+    pub name: String,
+    #[serde(default)]
+    pub translated_name: String,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    #[serde(default)]
+    pub gender: String,
+    #[serde(default)]
+    pub speech_register: String, // 說話語氣，例如 "敬語"、"粗魯"
+    #[serde(default)]
+    pub first_person_pronoun: String, // 例如 俺 / 僕 / 私
+    #[serde(default)]
+    pub relationships: Vec<Relationship>,
+}
+
+/// 以角色原名為 key 的角色表，隨章節累積
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(transparent)]
+pub struct CharacterRegistry(BTreeMap<String, Character>);
+
+impl CharacterRegistry {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn list(&self) -> Vec<&Character> {
+        self.0.values().collect()
+    }
+
+    /// 原名或任一別名出現在 text 中的角色
+    pub fn mentioned_in(&self, text: &str) -> Vec<&Character> {
+        self.0
+            .values()
+            .filter(|c| {
+                std::iter::once(&c.name)
+                    .chain(&c.aliases)
+                    .any(|name| !name.trim().is_empty() && text.contains(name.as_str()))
+            })
+            .collect()
+    }
+
+    // 依原名或別名尋找既有角色
+    fn key_for(&self, update: &Character) -> Option<String> {
+        let names: Vec<&str> = std::iter::once(update.name.as_str())
+            .chain(update.aliases.iter().map(String::as_str))
+            .collect();
+        self.0
+            .iter()
+            .find(|(key, existing)| {
+                names.iter().any(|name| {
+                    *name == key.as_str() || existing.aliases.iter().any(|alias| alias == name)
+                })
+            })
+            .map(|(key, _)| key.clone())
+    }
+
+    /// 合併本章的角色更新：空白欄位不覆蓋既有資料，別名與關係取聯集
+    pub fn merge(&mut self, updates: Vec<Character>) {
+        for update in updates {
+            if update.name.trim().is_empty() {
+                continue;
+            }
+            let Some(key) = self.key_for(&update) else {
+                self.0.insert(update.name.clone(), update);
+                continue;
+            };
+            let existing = self.0.get_mut(&key).unwrap();
+
+            for (field, value) in [
+                (&mut existing.translated_name, update.translated_name),
+                (&mut existing.gender, update.gender),
+                (&mut existing.speech_register, update.speech_register),
+                (
+                    &mut existing.first_person_pronoun,
+                    update.first_person_pronoun,
+                ),
+            ] {
+                if !value.trim().is_empty() {
+                    *field = value;
+                }
+            }
+
+            let new_aliases = std::iter::once(update.name).chain(update.aliases);
+            for alias in new_aliases {
+                if alias != key && !existing.aliases.contains(&alias) {
+                    existing.aliases.push(alias);
+                }
+            }
+
+            for relationship in update.relationships {
+                match existing
+                    .relationships
+                    .iter_mut()
+                    .find(|r| r.character == relationship.character)
+                {
+                    Some(current) => current.relation = relationship.relation,
+                    None => existing.relationships.push(relationship),
+                }
+            }
+        }
+    }
+}
+
+/// Pass 1 structured output 的角色 schema (strict 模式需列出所有欄位)
+pub fn character_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "object",
+        "properties": {
+            "name": { "type": "string" },
+            "translated_name": { "type": "string" },
+            "aliases": { "type": "array", "items": { "type": "string" } },
+            "gender": { "type": "string" },
+            "speech_register": { "type": "string" },
+            "first_person_pronoun": { "type": "string" },
+            "relationships": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "character": { "type": "string" },
+                        "relation": { "type": "string" }
+                    },
+                    "required": ["character", "relation"],
+                    "additionalProperties": false
+                }
+            }
+        },
+        "required": [
+            "name",
+            "translated_name",
+            "aliases",
+            "gender",
+            "speech_register",
+            "first_person_pronoun",
+            "relationships"
+        ],
+        "additionalProperties": false
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Character, CharacterRegistry, Relationship};
+
+    fn character(name: &str) -> Character {
+        Character {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn new_characters_are_added() {
+        let mut registry = CharacterRegistry::default();
+        registry.merge(vec![character("アリス"), character("")]);
+        assert_eq!(registry.len(), 1);
+    }
+
+    #[test]
+    fn updates_fill_fields_without_erasing_existing_data() {
+        let mut registry = CharacterRegistry::default();
+        registry.merge(vec![Character {
+            gender: "女".to_string(),
+            first_person_pronoun: "私".to_string(),
+            ..character("アリス")
+        }]);
+        registry.merge(vec![Character {
+            speech_register: "敬語".to_string(),
+            ..character("アリス")
+        }]);
+
+        let alice = registry.list()[0];
+        assert_eq!(alice.gender, "女");
+        assert_eq!(alice.first_person_pronoun, "私");
+        assert_eq!(alice.speech_register, "敬語");
+    }
+
+    #[test]
+    fn updates_by_alias_merge_into_the_same_character() {
+        let mut registry = CharacterRegistry::default();
+        registry.merge(vec![Character {
+            aliases: vec!["姫様".to_string()],
+            ..character("アリス")
+        }]);
+        registry.merge(vec![Character {
+            relationships: vec![Relationship {
+                character: "ボブ".to_string(),
+                relation: "護衛".to_string(),
+            }],
+            ..character("姫様")
+        }]);
+
+        assert_eq!(registry.len(), 1);
+        let alice = registry.list()[0];
+        assert_eq!(alice.name, "アリス");
+        assert_eq!(alice.relationships[0].relation, "護衛");
+    }
+
+    #[test]
+    fn characters_are_found_by_name_or_alias() {
+        let mut registry = CharacterRegistry::default();
+        registry.merge(vec![
+            Character {
+                aliases: vec!["姫様".to_string()],
+                ..character("アリス")
+            },
+            character("ボブ"),
+        ]);
+        let names = |text| -> Vec<String> {
+            registry
+                .mentioned_in(text)
+                .iter()
+                .map(|c| c.name.clone())
+                .collect()
+        };
+        assert_eq!(names("姫様が笑った。"), ["アリス"]);
+        assert_eq!(names("ボブとアリス"), ["アリス", "ボブ"]);
+        assert!(names("誰もいない").is_empty());
+    }
+}
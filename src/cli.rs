@@ -0,0 +1,114 @@
+// src/cli.rs
+
+use anyhow::{Result, bail};
+use std::path::PathBuf;
+
+use crate::toc::TocFormat;
+
+pub const USAGE: &str = "用法: ai-novel-translation [選項]
+
+選項:
+  --dry-run    只渲染 prompt 並估算 token 與費用，不呼叫 LLM
+  --no-cache   不讀取也不寫入回應快取
+  --refresh    忽略既有快取並以新回應覆寫
+  --import <檔案>  將整本小說切分成章節檔並寫入 input_folder (設定見 import 區塊)
+  --replay <稽核紀錄>  以 audit_log 記錄的回應取代 LLM，離線重現先前的執行
+  --toc <格式>  在 output_folder 輸出目錄檔 (markdown / json / csv)，不呼叫 LLM
+  -h, --help   顯示此說明";
+
+// 命令列參數
+#[derive(Debug, Default, PartialEq)]
+pub struct CliArgs {
+    pub dry_run: bool,
+    pub no_cache: bool,
+    pub refresh: bool,
+    pub import: Option<PathBuf>,
+    pub toc: Option<TocFormat>,
+    pub replay: Option<PathBuf>,
+    pub help: bool,
+}
+
+pub fn parse_args<I>(args: I) -> Result<CliArgs>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut cli = CliArgs::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dry-run" => cli.dry_run = true,
+            "--no-cache" => cli.no_cache = true,
+            "--refresh" => cli.refresh = true,
+            "--import" => match args.next() {
+                Some(path) => cli.import = Some(PathBuf::from(path)),
+                None => bail!("--import 需要指定檔案\n\n{}", USAGE),
+            },
+            "--replay" => match args.next() {
+                Some(path) => cli.replay = Some(PathBuf::from(path)),
+                None => bail!("--replay 需要指定稽核紀錄檔\n\n{}", USAGE),
+            },
+            "--toc" => match args.next().as_deref().map(TocFormat::parse) {
+                Some(Some(format)) => cli.toc = Some(format),
+                _ => bail!("--toc 需要指定格式 (markdown / json / csv)\n\n{}", USAGE),
+            },
+            "-h" | "--help" => cli.help = true,
+            other => bail!("未知的參數: {}\n\n{}", other, USAGE),
+        }
+    }
+    if cli.no_cache && cli.refresh {
+        bail!("--no-cache 與 --refresh 不能同時使用");
+    }
+    Ok(cli)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CliArgs, parse_args};
+    use crate::toc::TocFormat;
+
+    fn parse(args: &[&str]) -> anyhow::Result<CliArgs> {
+        parse_args(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn no_arguments_use_defaults() {
+        assert_eq!(parse(&[]).unwrap(), CliArgs::default());
+    }
+
+    #[test]
+    fn dry_run_flag_is_parsed() {
+        assert!(parse(&["--dry-run"]).unwrap().dry_run);
+    }
+
+    #[test]
+    fn cache_flags_are_mutually_exclusive() {
+        assert!(parse(&["--refresh"]).unwrap().refresh);
+        assert!(parse(&["--no-cache", "--refresh"]).is_err());
+    }
+
+    #[test]
+    fn import_takes_a_file_argument() {
+        let cli = parse(&["--import", "book.txt"]).unwrap();
+        assert_eq!(cli.import.unwrap().to_str(), Some("book.txt"));
+        assert!(parse(&["--import"]).is_err());
+    }
+
+    #[test]
+    fn replay_takes_an_audit_log() {
+        let cli = parse(&["--replay", "logs/audit.jsonl"]).unwrap();
+        assert_eq!(cli.replay.unwrap().to_str(), Some("logs/audit.jsonl"));
+        assert!(parse(&["--replay"]).is_err());
+    }
+
+    #[test]
+    fn toc_takes_a_format_argument() {
+        assert_eq!(parse(&["--toc", "csv"]).unwrap().toc, Some(TocFormat::Csv));
+        assert!(parse(&["--toc", "pdf"]).is_err());
+        assert!(parse(&["--toc"]).is_err());
+    }
+
+    #[test]
+    fn unknown_argument_is_rejected() {
+        assert!(parse(&["--bogus"]).is_err());
+    }
+}
@@ -0,0 +1,700 @@
+// src/document.rs
+
+use anyhow::{Result, bail};
+use lol_html::html_content::ContentType;
+use lol_html::{RewriteStrSettings, element, rewrite_str};
+use regex::Regex;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use crate::bilingual::{align, escape_html};
+use crate::docx::Docx;
+use crate::encoding::EncodingConfig;
+use crate::notes::{self, Note};
+use crate::ruby::{self, Reading};
+use crate::text::paragraphs;
+
+/// 章節檔的格式 (依副檔名)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputFormat {
+    Text,
+    Markdown,
+    Html,
+    Docx,
+}
+
+impl InputFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "txt" => Some(InputFormat::Text),
+            "md" | "markdown" => Some(InputFormat::Markdown),
+            "html" | "htm" | "xhtml" => Some(InputFormat::Html),
+            "docx" => Some(InputFormat::Docx),
+            _ => None,
+        }
+    }
+
+    /// 模板變數 input_format 的值
+    pub fn name(self) -> &'static str {
+        match self {
+            InputFormat::Text => "text",
+            InputFormat::Markdown => "markdown",
+            InputFormat::Html => "html",
+            InputFormat::Docx => "docx",
+        }
+    }
+}
+
+// 原樣保留的片段，或需要翻譯的段落 (prefix 為 Markdown 的標題、清單或引用符號)
+#[derive(Debug, PartialEq)]
+enum Part {
+    Raw(String),
+    Text { prefix: String, text: String },
+    Row(Vec<String>), // Markdown 表格的一列，每格各自翻譯 (空白格除外)
+}
+
+/// 章節內容：純文字直接翻譯，Markdown/HTML 只翻譯段落文字並保留其餘結構
+#[derive(Debug)]
+pub struct Document {
+    format: InputFormat,
+    plain: String, // 純文字章節的全文
+    parts: Vec<Part>,
+    docx: Option<Docx>,
+}
+
+// HTML 中不屬於正文的元素 (導覽、腳本、廣告)
+const HTML_JUNK: &str = "script, style, noscript, template, iframe, nav, header, footer, aside, form, \
+    button, select, .ad, .ads, .advert, .advertisement, [class*=\"advert\"], [id*=\"advert\"], \
+    [class^=\"ad-\"], [id^=\"ad-\"], [class*=\"sponsor\"]";
+
+// 需要翻譯的區塊元素；包含其他區塊的元素視為容器，只翻譯最內層
+const HTML_BLOCKS: &str =
+    "p, h1, h2, h3, h4, h5, h6, li, blockquote, dt, dd, td, th, caption, figcaption, div";
+
+const BLOCK_START: char = '\u{E000}';
+const BLOCK_END: char = '\u{E001}';
+
+static MARKDOWN_PREFIX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(\s*(?:>\s?)*\s*(?:#{1,6}\s+|[-*+]\s+|\d+[.)]\s+)?)(.*)$").unwrap()
+});
+static MARKDOWN_RAW_LINE: LazyLock<Regex> = LazyLock::new(|| {
+    // 分隔線、只有圖片的行、表格的對齊列、連結定義
+    Regex::new(concat!(
+        r"^(?:(?:[-*_]\s*){3,}|!\[[^\]]*\]\([^)]*\)|\[[^\]]+\]:\s.*",
+        r"|\|?\s*:?-+:?\s*(?:\|\s*:?-+:?\s*)*\|?)$",
+    ))
+    .unwrap()
+});
+// 表格的對齊列，需含 | 才不會與分隔線混淆
+static TABLE_DELIMITER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\|?\s*:?-+:?\s*(?:\|\s*:?-+:?\s*)*\|?$").unwrap());
+static HTML_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]*>").unwrap());
+static BODY_END: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)</body\s*>").unwrap());
+static WHITESPACE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\s+").unwrap());
+
+// 合併多行段落：兩側皆非 ASCII (例如中日文) 時不加空白
+fn join_lines(first: &str, second: &str) -> String {
+    let ascii_boundary =
+        first.ends_with(|c: char| c.is_ascii()) || second.starts_with(|c: char| c.is_ascii());
+    if ascii_boundary {
+        format!("{} {}", first, second)
+    } else {
+        format!("{}{}", first, second)
+    }
+}
+
+fn is_list_or_heading(prefix: &str) -> bool {
+    prefix
+        .trim()
+        .trim_start_matches(['>', ' '])
+        .chars()
+        .next()
+        .is_some()
+}
+
+// "| a | b\\|c |" -> ["a", "b|c"]；寫回時再跳脫 |
+fn table_cells(line: &str) -> Vec<String> {
+    let inner = line.strip_prefix('|').unwrap_or(line);
+    let inner = match inner.strip_suffix('|') {
+        Some(rest) if !rest.ends_with('\\') => rest,
+        _ => inner,
+    };
+    let mut cells = vec![String::new()];
+    let mut chars = inner.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                cells.last_mut().unwrap().push(chars.next().unwrap())
+            }
+            '|' => cells.push(String::new()),
+            _ => cells.last_mut().unwrap().push(c),
+        }
+    }
+    cells.iter().map(|cell| cell.trim().to_string()).collect()
+}
+
+fn is_table_delimiter(line: &str) -> bool {
+    let line = line.trim();
+    line.contains('|') && TABLE_DELIMITER.is_match(line)
+}
+
+fn parse_markdown(text: &str) -> Vec<Part> {
+    let mut parts = Vec::new();
+    let mut fence: Option<&str> = None;
+    let mut front_matter = false;
+    // GFM 表格：標題列的下一行為對齊列，之後到空行為止都是表格列 (行首不一定有 |)
+    let mut table = false;
+    let lines: Vec<&str> = text.lines().collect();
+    for (index, line) in lines.iter().copied().enumerate() {
+        let trimmed = line.trim();
+        if index == 0 && trimmed == "---" {
+            front_matter = true;
+            parts.push(Part::Raw(line.to_string()));
+            continue;
+        }
+        if front_matter {
+            front_matter = trimmed != "---" && trimmed != "...";
+            parts.push(Part::Raw(line.to_string()));
+            continue;
+        }
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+            parts.push(Part::Raw(line.to_string()));
+            continue;
+        }
+        if let Some(marker) = ["```", "~~~"].into_iter().find(|m| trimmed.starts_with(m)) {
+            fence = Some(marker);
+            parts.push(Part::Raw(line.to_string()));
+            continue;
+        }
+        // 只有標籤的 HTML 行 (例如 <div>、<br>) 原樣保留，含文字的與一般段落一樣翻譯
+        let markup_only = trimmed.starts_with('<') && !html_has_text(trimmed);
+        if trimmed.is_empty() {
+            table = false;
+        }
+        if trimmed.is_empty() || markup_only || MARKDOWN_RAW_LINE.is_match(trimmed) {
+            parts.push(Part::Raw(line.to_string()));
+            continue;
+        }
+        if !table
+            && trimmed.contains('|')
+            && lines
+                .get(index + 1)
+                .is_some_and(|next| is_table_delimiter(next))
+        {
+            table = true;
+        }
+        if table || trimmed.starts_with('|') {
+            parts.push(Part::Row(table_cells(trimmed)));
+            continue;
+        }
+
+        let captures = MARKDOWN_PREFIX.captures(line).unwrap();
+        let prefix = captures[1].to_string();
+        let body = captures[2].trim();
+        // 同一段落的後續行併入前一行
+        if let Some(Part::Text {
+            prefix: previous_prefix,
+            text,
+        }) = parts.last_mut()
+            && !is_list_or_heading(&prefix)
+            && !previous_prefix.trim_start().starts_with('#')
+            && previous_prefix
+                .trim()
+                .trim_start_matches(['>', ' '])
+                .is_empty()
+            && previous_prefix.trim() == prefix.trim()
+        {
+            *text = join_lines(text, body);
+            continue;
+        }
+        parts.push(Part::Text {
+            prefix,
+            text: body.to_string(),
+        });
+    }
+    parts
+}
+
+fn html_has_text(inner: &str) -> bool {
+    !HTML_TAG
+        .replace_all(inner, "")
+        .replace("&nbsp;", "")
+        .trim()
+        .is_empty()
+}
+
+const HTTP_EQUIV_UTF8: &str = "text/html; charset=utf-8";
+
+// 解析時兩種 charset 宣告都已改為 utf-8，寫檔時改成實際輸出的編碼
+fn declare_charset(html: &str, charset: &str) -> String {
+    html.replacen("charset=\"utf-8\"", &format!("charset=\"{}\"", charset), 1)
+        .replacen(
+            HTTP_EQUIV_UTF8,
+            &format!("text/html; charset={}", charset),
+            1,
+        )
+}
+
+// 以標記包住每個區塊元素的內容，再取出最內層的區塊
+fn parse_html(html: &str) -> Result<Vec<Part>> {
+    let marked = rewrite_str(
+        html,
+        RewriteStrSettings {
+            element_content_handlers: vec![
+                element!(HTML_JUNK, |el| {
+                    el.remove();
+                    Ok(())
+                }),
+                // 寫檔時再依 encoding.output 替換 (見 declare_charset)
+                element!("meta[charset]", |el| {
+                    el.set_attribute("charset", "utf-8")?;
+                    Ok(())
+                }),
+                // 舊網頁常用 <meta http-equiv="Content-Type" content="text/html; charset=Shift_JIS">
+                element!("meta[http-equiv]", |el| {
+                    let content_type = el
+                        .get_attribute("http-equiv")
+                        .is_some_and(|value| value.eq_ignore_ascii_case("content-type"));
+                    if content_type {
+                        el.set_attribute("content", HTTP_EQUIV_UTF8)?;
+                    }
+                    Ok(())
+                }),
+                element!(HTML_BLOCKS, |el| {
+                    el.prepend(&BLOCK_START.to_string(), ContentType::Html);
+                    el.append(&BLOCK_END.to_string(), ContentType::Html);
+                    Ok(())
+                }),
+            ],
+            ..RewriteStrSettings::new()
+        },
+    )?;
+
+    // (內容起點, 是否包含其他區塊)
+    let mut stack: Vec<(usize, bool)> = Vec::new();
+    let mut leaves: Vec<(usize, usize)> = Vec::new();
+    for (index, c) in marked.char_indices() {
+        match c {
+            BLOCK_START => {
+                if let Some(parent) = stack.last_mut() {
+                    parent.1 = true;
+                }
+                stack.push((index + c.len_utf8(), false));
+            }
+            BLOCK_END => {
+                if let Some((start, has_children)) = stack.pop()
+                    && !has_children
+                {
+                    leaves.push((start, index));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let strip = |s: &str| s.replace([BLOCK_START, BLOCK_END], "");
+    let mut parts = Vec::new();
+    let mut position = 0;
+    for (start, end) in leaves {
+        let inner = &marked[start..end];
+        if !html_has_text(inner) {
+            continue;
+        }
+        parts.push(Part::Raw(strip(&marked[position..start])));
+        parts.push(Part::Text {
+            prefix: String::new(),
+            text: WHITESPACE.replace_all(inner.trim(), " ").to_string(),
+        });
+        position = end;
+    }
+    parts.push(Part::Raw(strip(&marked[position..])));
+    Ok(parts)
+}
+
+impl Document {
+    pub fn parse(format: InputFormat, text: &str) -> Result<Self> {
+        let parts = match format {
+            InputFormat::Text => Vec::new(),
+            InputFormat::Markdown => parse_markdown(text),
+            InputFormat::Html => parse_html(text)?,
+            InputFormat::Docx => bail!(".docx 不是文字檔，請使用 Document::from_docx"),
+        };
+        Ok(Self {
+            format,
+            plain: text.to_string(),
+            parts,
+            docx: None,
+        })
+    }
+
+    pub fn from_docx(package: Vec<u8>) -> Result<Self> {
+        Ok(Self {
+            format: InputFormat::Docx,
+            plain: String::new(),
+            parts: Vec::new(),
+            docx: Some(Docx::parse(package)?),
+        })
+    }
+
+    fn blocks(&self) -> Vec<&str> {
+        if let Some(docx) = &self.docx {
+            return docx.paragraphs();
+        }
+        self.parts
+            .iter()
+            .flat_map(|part| match part {
+                Part::Text { text, .. } => vec![text.as_str()],
+                Part::Row(cells) => cells
+                    .iter()
+                    .filter(|cell| !cell.is_empty())
+                    .map(String::as_str)
+                    .collect(),
+                Part::Raw(_) => Vec::new(),
+            })
+            .collect()
+    }
+
+    /// 交給 LLM 的文字：Markdown/HTML 每個段落一行，段落間空一行
+    pub fn source_text(&self) -> String {
+        match self.format {
+            InputFormat::Text => self.plain.clone(),
+            _ => self.blocks().join("\n\n"),
+        }
+    }
+
+    // 將譯文段落分配給原文的每個段落；段落數不同時依長度對齊
+    fn assign(&self, translation: &str) -> Vec<String> {
+        let blocks = self.blocks();
+        let translated = paragraphs(translation);
+        let mut assigned: Vec<String> = Vec::with_capacity(blocks.len());
+        if blocks.len() == translated.len() {
+            assigned.extend(translated.iter().map(|t| t.to_string()));
+        } else {
+            for pair in align(&blocks, &translated) {
+                let text = pair
+                    .translation
+                    .split('\n')
+                    .map(str::to_string)
+                    .reduce(|a, b| join_lines(&a, &b))
+                    .unwrap_or_default();
+                match pair.source.lines().count() {
+                    // 多出的譯文段落併入前一段
+                    0 => match assigned.last_mut() {
+                        Some(last) => *last = join_lines(last, &text),
+                        None => assigned.push(text),
+                    },
+                    count => {
+                        assigned.push(text);
+                        assigned.extend(std::iter::repeat_n(String::new(), count - 1));
+                    }
+                }
+            }
+        }
+        assigned
+    }
+
+    /// 將譯文依段落順序填回原本的結構
+    pub fn render(&self, translation: &str) -> String {
+        if self.format == InputFormat::Text {
+            return translation.to_string();
+        }
+        let mut translated = self.assign(translation).into_iter();
+        let rendered: Vec<String> = self
+            .parts
+            .iter()
+            .map(|part| match part {
+                Part::Raw(raw) => raw.clone(),
+                Part::Text { prefix, .. } => {
+                    format!("{}{}", prefix, translated.next().unwrap_or_default())
+                }
+                Part::Row(cells) => {
+                    let cells: Vec<String> = cells
+                        .iter()
+                        .map(|cell| {
+                            if cell.is_empty() {
+                                String::new()
+                            } else {
+                                translated.next().unwrap_or_default().replace('|', "\\|")
+                            }
+                        })
+                        .collect();
+                    format!("| {} |", cells.join(" | "))
+                }
+            })
+            .collect();
+        match self.format {
+            InputFormat::Markdown => rendered.join("\n") + "\n",
+            _ => rendered.concat(),
+        }
+    }
+
+    /// 在譯文中加上譯註標記，並在最後加上譯註區塊；HTML 另外加回讀音標註 (ruby)
+    pub fn render_with_notes(
+        &self,
+        translation: &str,
+        notes: &[Note],
+        heading: &str,
+        ruby: &[Reading],
+    ) -> String {
+        let (marked, placed) = notes::place(translation, notes, |n| match self.format {
+            InputFormat::Markdown => format!("[^{}]", n),
+            InputFormat::Html => format!(
+                "<sup class=\"translator-note-ref\"><a id=\"tnref-{n}\" href=\"#tn-{n}\">[{n}]</a></sup>"
+            ),
+            _ => format!("[{}]", n),
+        });
+        // 譯註標記放好後才加上 ruby，避免 anchor 被標籤隔開
+        let marked = match self.format {
+            InputFormat::Html => ruby::annotate_html(&marked, ruby),
+            _ => marked,
+        };
+        let rendered = self.render(&marked);
+        if placed.is_empty() {
+            return rendered;
+        }
+        match self.format {
+            InputFormat::Markdown => {
+                // 有標記的譯註使用 Markdown 註腳，其餘列在譯註標題下
+                let mut out = rendered;
+                let (anchored, endnotes): (Vec<_>, Vec<_>) =
+                    placed.iter().partition(|p| p.anchored);
+                if !anchored.is_empty() {
+                    out.push('\n');
+                    for p in anchored {
+                        out.push_str(&format!("[^{}]: {}\n", p.number, p.note.note));
+                    }
+                }
+                if !endnotes.is_empty() {
+                    out.push_str(&format!("\n---\n\n## {}\n\n", heading));
+                    for p in endnotes {
+                        out.push_str(&format!("- {}\n", p.note.note));
+                    }
+                }
+                out
+            }
+            InputFormat::Html => {
+                let items: String = placed
+                    .iter()
+                    .map(|p| {
+                        let back = if p.anchored {
+                            format!(" <a href=\"#tnref-{}\">↩</a>", p.number)
+                        } else {
+                            String::new()
+                        };
+                        format!(
+                            "<li id=\"tn-{}\" value=\"{}\">{}{}</li>",
+                            p.number,
+                            p.number,
+                            escape_html(&p.note.note),
+                            back
+                        )
+                    })
+                    .collect();
+                let aside = format!(
+                    "<aside class=\"translator-notes\"><h2>{}</h2><ol>{}</ol></aside>\n",
+                    escape_html(heading),
+                    items
+                );
+                match BODY_END.find_iter(&rendered).last().map(|m| m.start()) {
+                    Some(at) => format!("{}{}{}", &rendered[..at], aside, &rendered[at..]),
+                    None => rendered + &aside,
+                }
+            }
+            _ => {
+                let items: String = placed
+                    .iter()
+                    .map(|p| format!("[{}] {}\n", p.number, p.note.note))
+                    .collect();
+                format!("{}\n\n{}\n{}", rendered.trim_end(), heading, items)
+            }
+        }
+    }
+
+    /// 寫出譯文與譯註：.docx 直接產生 Word 文件 (譯註為文末段落)，其他格式依 encoding 設定寫成文字檔
+    pub fn write(
+        &self,
+        path: &Path,
+        translation: &str,
+        notes: &[Note],
+        heading: &str,
+        ruby: &[Reading],
+        encoding: &EncodingConfig,
+    ) -> Result<()> {
+        match &self.docx {
+            Some(docx) => {
+                let (marked, placed) = notes::place(translation, notes, |n| format!("[{}]", n));
+                let appendix: Vec<String> = if placed.is_empty() {
+                    Vec::new()
+                } else {
+                    std::iter::once(heading.to_string())
+                        .chain(
+                            placed
+                                .iter()
+                                .map(|p| format!("[{}] {}", p.number, p.note.note)),
+                        )
+                        .collect()
+                };
+                let package = docx.render(&self.assign(&marked), &appendix)?;
+                Ok(fs_err::write(path, package)?)
+            }
+            None => {
+                let mut rendered = self.render_with_notes(translation, notes, heading, ruby);
+                if self.format == InputFormat::Html {
+                    rendered = declare_charset(&rendered, &encoding.html_charset());
+                }
+                encoding.write_output(path, &rendered)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Document, InputFormat, Note, declare_charset};
+    use crate::ruby;
+    use std::collections::HashMap;
+
+    #[test]
+    fn markdown_keeps_structure_and_replaces_text() {
+        let source = "---\ntitle: x\n---\n# 第一話\n\n彼は**走った**。\nそして止まった。\n\n- 項目\n\n![挿絵](a.png)\n\n```\ncode\n```\n";
+        let doc = Document::parse(InputFormat::Markdown, source).unwrap();
+        assert_eq!(
+            doc.source_text(),
+            "第一話\n\n彼は**走った**。そして止まった。\n\n項目"
+        );
+        let rendered = doc.render("第一話\n\n他**跑了**，然後停下。\n\n項目");
+        assert_eq!(
+            rendered,
+            "---\ntitle: x\n---\n# 第一話\n\n他**跑了**，然後停下。\n\n- 項目\n\n![挿絵](a.png)\n\n```\ncode\n```\n"
+        );
+    }
+
+    #[test]
+    fn html_drops_junk_and_translates_leaf_blocks() {
+        let source = "<html><head><meta charset=\"Shift_JIS\"><script>x()</script></head><body>\
+            <nav>目次</nav><div class=\"ad-banner\">広告</div>\
+            <div id=\"honbun\"><h1>第一話</h1><p>彼は<ruby>剣<rt>つるぎ</rt></ruby>を\n抜いた。</p><p><img src=\"a.png\"></p></div>\
+            </body></html>";
+        let doc = Document::parse(InputFormat::Html, source).unwrap();
+        assert_eq!(
+            doc.source_text(),
+            "第一話\n\n彼は<ruby>剣<rt>つるぎ</rt></ruby>を 抜いた。"
+        );
+        // ruby 不交給模型，寫出時以譯名加回
+        let (content, readings) = ruby::strip(&doc.source_text(), true);
+        assert_eq!(content, "第一話\n\n彼は剣を 抜いた。");
+        let terms = HashMap::from([("剣".to_string(), "劍".to_string())]);
+        let readings = ruby::translated(&readings, &terms);
+        let rendered = doc.render_with_notes("第一話\n\n他拔出了劍。", &[], "譯註", &readings);
+        assert!(rendered.contains("<h1>第一話</h1><p>他拔出了<ruby>劍<rt>つるぎ</rt></ruby>。</p><p><img src=\"a.png\"></p>"));
+        assert!(rendered.contains("charset=\"utf-8\""));
+        assert!(
+            !rendered.contains("目次") && !rendered.contains("広告") && !rendered.contains("x()")
+        );
+    }
+
+    #[test]
+    fn markdown_table_cells_and_html_lines_are_translated() {
+        let source = "| 名前 | 年齢 |\n| --- | :-: |\n| 太郎\\|タロウ || 20 |\n\n<div class=\"note\">\n<span>彼は走った。</span>\n</div>\n";
+        let doc = Document::parse(InputFormat::Markdown, source).unwrap();
+        assert_eq!(
+            doc.source_text(),
+            "名前\n\n年齢\n\n太郎|タロウ\n\n20\n\n<span>彼は走った。</span>"
+        );
+        let rendered = doc.render("名字\n\n年齡\n\n太郎|塔羅\n\n20\n\n<span>他跑了。</span>");
+        assert_eq!(
+            rendered,
+            "| 名字 | 年齡 |\n| --- | :-: |\n| 太郎\\|塔羅 |  | 20 |\n\n<div class=\"note\">\n<span>他跑了。</span>\n</div>\n"
+        );
+    }
+
+    #[test]
+    fn tables_without_leading_pipes_are_found_by_the_delimiter_row() {
+        let source = "名前 | 年齢\n--- | :-:\n太郎 | 20\n\n本文 | 続き\n";
+        let doc = Document::parse(InputFormat::Markdown, source).unwrap();
+        assert_eq!(
+            doc.source_text(),
+            "名前\n\n年齢\n\n太郎\n\n20\n\n本文 | 続き"
+        );
+        assert_eq!(
+            doc.render("名字\n\n年齡\n\n太郎\n\n20\n\n正文 | 後續"),
+            "| 名字 | 年齡 |\n--- | :-:\n| 太郎 | 20 |\n\n正文 | 後續\n"
+        );
+        // 沒有 | 的 --- 是分隔線，不是表格
+        let doc = Document::parse(InputFormat::Markdown, "本文\n\n---\n\n続き\n").unwrap();
+        assert_eq!(doc.source_text(), "本文\n\n続き");
+    }
+
+    #[test]
+    fn http_equiv_charset_follows_the_output_encoding() {
+        let source = "<html><head><meta http-equiv=\"Content-Type\" content=\"text/html; charset=Shift_JIS\">\
+            </head><body><p>本文</p></body></html>";
+        let doc = Document::parse(InputFormat::Html, source).unwrap();
+        let rendered = doc.render("正文");
+        assert!(!rendered.contains("Shift_JIS"));
+        assert!(
+            declare_charset(&rendered, "big5")
+                .contains("<meta http-equiv=\"Content-Type\" content=\"text/html; charset=big5\">")
+        );
+        assert!(declare_charset("<meta charset=\"utf-8\">", "gbk").contains("charset=\"gbk\""));
+    }
+
+    #[test]
+    fn extra_translated_paragraphs_are_merged() {
+        let doc = Document::parse(InputFormat::Markdown, "# 見出し\n\n長い段落です。\n").unwrap();
+        let rendered = doc.render("標題\n\n很長的\n\n段落。");
+        assert!(rendered.starts_with("# 標題\n\n"));
+        assert_eq!(rendered.lines().count(), 3);
+    }
+
+    #[test]
+    fn notes_are_rendered_in_each_format() {
+        let notes = [
+            Note {
+                paragraph: 1,
+                anchor: "劍".to_string(),
+                note: "古代兵器".to_string(),
+            },
+            Note::endnote("劍：剣（つるぎ）".to_string()),
+        ];
+        let doc = Document::parse(InputFormat::Text, "剣").unwrap();
+        assert_eq!(
+            doc.render_with_notes("劍\n", &notes, "譯註", &[]),
+            "劍[1]\n\n譯註\n[1] 古代兵器\n[2] 劍：剣（つるぎ）\n"
+        );
+        let doc = Document::parse(InputFormat::Markdown, "剣\n").unwrap();
+        assert_eq!(
+            doc.render_with_notes("劍", &notes, "譯註", &[]),
+            "劍[^1]\n\n[^1]: 古代兵器\n\n---\n\n## 譯註\n\n- 劍：剣（つるぎ）\n"
+        );
+        assert!(
+            doc.render_with_notes("劍", &notes, "Translator's Notes", &[])
+                .contains("\n## Translator's Notes\n")
+        );
+        let doc =
+            Document::parse(InputFormat::Html, "<html><body><p>剣</p></body></html>").unwrap();
+        let html = doc.render_with_notes("劍", &notes, "譯註", &[]);
+        assert!(html.contains("<p>劍<sup class=\"translator-note-ref\"><a id=\"tnref-1\" href=\"#tn-1\">[1]</a></sup></p>"));
+        assert!(
+            html.contains(
+                "<li id=\"tn-2\" value=\"2\">劍：剣（つるぎ）</li></ol></aside>\n</body>"
+            )
+        );
+        // 小寫後長度會改變的字元 (İ) 不影響插入位置
+        let doc =
+            Document::parse(InputFormat::Html, "<html><BODY><p>İİ剣</p></BODY ></html>").unwrap();
+        let html = doc.render_with_notes("İİ劍", &notes, "譯註", &[]);
+        assert!(html.contains("</ol></aside>\n</BODY ></html>"));
+    }
+
+    #[test]
+    fn plain_text_is_passed_through() {
+        let doc = Document::parse(InputFormat::Text, "a\n\nb").unwrap();
+        assert_eq!(doc.source_text(), "a\n\nb");
+        assert_eq!(doc.render("甲\n\n乙"), "甲\n\n乙");
+    }
+}
@@ -0,0 +1,586 @@
+// src/docx.rs
+
+use anyhow::{Context, Result, bail};
+use quick_xml::Reader;
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+use regex::Regex;
+use std::io::{Cursor, Read, Write};
+use std::sync::LazyLock;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+const DOCUMENT_XML: &str = "word/document.xml";
+
+// 交給 LLM 的標籤：樣式 <s1>…</s1>、段落內換行 <br/>、原樣保留的物件 <x1/>
+static TAG: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<s(\d+)>|</s\d+>|<br\s*/?>|<x(\d+)\s*/>").unwrap());
+static ADJACENT_STYLES: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"</s(\d+)><s(\d+)>").unwrap());
+
+// 只含文字的 run 中允許出現的元素；出現其他元素 (圖片、註腳參照等) 的 run 原樣保留
+const TEXT_RUN_ELEMENTS: [&[u8]; 19] = [
+    b"w:t",
+    b"w:tab",
+    b"w:br",
+    b"w:cr",
+    b"w:noBreakHyphen",
+    b"w:softHyphen",
+    b"w:lastRenderedPageBreak",
+    b"w:rPr",
+    b"w:r",
+    // ruby (讀音) 以 <ruby>本文<rt>讀音</rt></ruby> 交給 ruby 模組處理
+    b"w:ruby",
+    b"w:rubyPr",
+    b"w:rubyAlign",
+    b"w:hps",
+    b"w:hpsRaise",
+    b"w:hpsBaseText",
+    b"w:lid",
+    b"w:rt",
+    b"w:rubyBase",
+    b"w:dirty",
+];
+
+// 包住 run 的元素：超連結與修訂的插入/移入。譯文的 run 也放回同樣的元素中
+const RUN_WRAPPERS: [&[u8]; 3] = [b"w:hyperlink", b"w:ins", b"w:moveTo"];
+
+// 段落中 run 以外可以省略的元素 (拼字檢查標記)
+const DROPPED_ELEMENTS: [&[u8]; 1] = [b"w:proofErr"];
+
+/// run 的樣式：文字格式 (w:rPr) 加上外層的超連結或修訂元素
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Style {
+    open: String, // 外層元素的開始標籤
+    rpr: String,
+    close: String,
+}
+
+impl Style {
+    fn wrap(&self, runs: &str) -> String {
+        format!("{}{}{}", self.open, runs, self.close)
+    }
+}
+
+/// 可翻譯的段落：styles[0] 為段落中最常見的樣式，其他樣式以 <sN> 標記
+#[derive(Debug)]
+struct Paragraph {
+    open: String,  // <w:p ...>
+    props: String, // <w:pPr>
+    styles: Vec<Style>,
+    objects: Vec<String>, // 原樣保留的 run 與書籤等元素，以 <xN/> 標記位置
+    source: String,       // 加上標籤的原文
+}
+
+enum Run {
+    Text { style: Style, text: String },
+    Object(String),
+}
+
+// 目前所在的外層元素 (開始標籤, 結束標籤, 深度)
+struct Wrapper {
+    open: String,
+    close: String,
+    depth: usize,
+}
+
+fn wrapper_style(wrappers: &[Wrapper], rpr: String) -> Style {
+    Style {
+        open: wrappers.iter().map(|w| w.open.as_str()).collect(),
+        rpr,
+        close: wrappers.iter().rev().map(|w| w.close.as_str()).collect(),
+    }
+}
+
+// 讀取中的 run
+struct OpenRun {
+    start: usize,
+    depth: usize,
+    style: String,
+    text: String,
+    reading: Option<String>,
+    object: bool,
+}
+
+#[derive(Debug)]
+enum Part {
+    Raw(String),
+    Paragraph(Paragraph),
+}
+
+/// Word 文件：只改寫 word/document.xml 中的段落文字，其餘檔案原樣複製
+#[derive(Debug)]
+pub struct Docx {
+    package: Vec<u8>,
+    parts: Vec<Part>,
+}
+
+fn position(reader: &Reader<&[u8]>) -> usize {
+    reader.buffer_position() as usize
+}
+
+// 解析單一段落；沒有文字或包含巢狀段落 (文字方塊) 時回傳 None
+// 超連結與修訂的插入保留為 run 的外層元素，書籤等標記與其他不認得的元素整個當成物件保留
+fn parse_paragraph(xml: &str) -> Result<Option<Paragraph>> {
+    let mut reader = Reader::from_str(xml);
+    let mut depth = 0;
+    let mut open = String::new();
+    let mut props = String::new();
+    let mut runs: Vec<Run> = Vec::new();
+    let mut run: Option<OpenRun> = None;
+    let mut wrappers: Vec<Wrapper> = Vec::new();
+    let mut opaque: Option<(usize, usize)> = None; // 原樣保留的元素 (起點, 深度)
+    let mut rpr_start = None;
+    let mut ppr_start = None;
+    let mut in_text = false;
+    let mut in_reading = false;
+
+    loop {
+        let start = position(&reader);
+        let event = reader.read_event()?;
+        let end = position(&reader);
+        let outside_runs = run.is_none() && opaque.is_none() && ppr_start.is_none() && depth > 0;
+        match event {
+            Event::Start(e) => {
+                let name = e.name();
+                match (depth, name.as_ref(), run.as_mut()) {
+                    (0, _, _) => open = xml[start..end].to_string(),
+                    (_, b"w:p", _) => return Ok(None),
+                    _ if opaque.is_some() || ppr_start.is_some() => {}
+                    (1, b"w:pPr", None) => ppr_start = Some(start),
+                    (_, name, None) if RUN_WRAPPERS.contains(&name) => wrappers.push(Wrapper {
+                        open: xml[start..end].to_string(),
+                        close: format!("</{}>", String::from_utf8_lossy(name)),
+                        depth,
+                    }),
+                    (_, b"w:r", None) => {
+                        run = Some(OpenRun {
+                            start,
+                            depth,
+                            style: String::new(),
+                            text: String::new(),
+                            reading: None,
+                            object: false,
+                        })
+                    }
+                    (_, _, None) => opaque = Some((start, depth)),
+                    (_, _, Some(r)) if r.object || rpr_start.is_some() => {}
+                    (_, b"w:t", Some(_)) => in_text = true,
+                    (_, _, Some(_)) if in_reading => {}
+                    (_, b"w:rPr", Some(_)) => rpr_start = Some(start),
+                    (_, b"w:ruby", Some(r)) => r.reading = Some(String::new()),
+                    (_, b"w:rt", Some(_)) => in_reading = true,
+                    (_, name, Some(r)) => r.object = !TEXT_RUN_ELEMENTS.contains(&name),
+                }
+                depth += 1;
+            }
+            Event::Empty(e) => {
+                let name = e.name();
+                match (name.as_ref(), run.as_mut()) {
+                    (b"w:pPr", None) if depth == 1 => props = xml[start..end].to_string(),
+                    (name, None) if outside_runs && !DROPPED_ELEMENTS.contains(&name) => {
+                        let style = wrapper_style(&wrappers, String::new());
+                        runs.push(Run::Object(style.wrap(&xml[start..end])));
+                    }
+                    (_, Some(r)) if r.object || rpr_start.is_some() || in_reading => {}
+                    (b"w:rPr", Some(r)) => r.style = xml[start..end].to_string(),
+                    (b"w:tab", Some(r)) => r.text.push('\t'),
+                    (b"w:br" | b"w:cr", Some(r)) => r.text.push('\n'),
+                    (b"w:noBreakHyphen", Some(r)) => r.text.push('\u{2011}'),
+                    (name, Some(r)) => r.object = !TEXT_RUN_ELEMENTS.contains(&name),
+                    _ => {}
+                }
+            }
+            Event::Text(e) if in_text => {
+                if let Some(r) = run.as_mut() {
+                    match r.reading.as_mut() {
+                        Some(reading) if in_reading => reading.push_str(&e.unescape()?),
+                        _ => r.text.push_str(&e.unescape()?),
+                    }
+                }
+            }
+            Event::End(e) => {
+                depth -= 1;
+                match (e.name().as_ref(), run.as_mut()) {
+                    (_, None) if opaque.is_some_and(|(_, d)| d == depth) => {
+                        let (s, _) = opaque.take().unwrap();
+                        let style = wrapper_style(&wrappers, String::new());
+                        runs.push(Run::Object(style.wrap(&xml[s..end])));
+                    }
+                    (_, None) if opaque.is_some() => {}
+                    (b"w:pPr", None) if depth == 1 => {
+                        if let Some(s) = ppr_start.take() {
+                            props = xml[s..end].to_string();
+                        }
+                    }
+                    (_, None) if wrappers.last().is_some_and(|w| w.depth == depth) => {
+                        wrappers.pop();
+                    }
+                    (b"w:r", Some(r)) if r.depth == depth => {
+                        let mut r = run.take().unwrap();
+                        if let Some(reading) = r.reading.take() {
+                            r.text = format!("<ruby>{}<rt>{}</rt></ruby>", r.text, reading);
+                        }
+                        let style = wrapper_style(&wrappers, r.style);
+                        if r.object {
+                            let raw = xml[r.start..end].to_string();
+                            runs.push(Run::Object(style.wrap(&raw)));
+                        } else if !r.text.is_empty() {
+                            runs.push(Run::Text {
+                                style,
+                                text: r.text,
+                            });
+                        }
+                    }
+                    (_, Some(r)) if r.object => {}
+                    (b"w:t", Some(_)) => in_text = false,
+                    (b"w:rt", Some(_)) => in_reading = false,
+                    (b"w:rPr", Some(r)) => {
+                        if let Some(s) = rpr_start.take() {
+                            r.style = xml[s..end].to_string();
+                        }
+                    }
+                    _ => {}
+                }
+                if depth == 0 {
+                    break;
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    let texts: Vec<(&Style, &String)> = runs
+        .iter()
+        .filter_map(|run| match run {
+            Run::Text { style, text } => Some((style, text)),
+            Run::Object(_) => None,
+        })
+        .collect();
+    if texts.iter().all(|(_, text)| text.trim().is_empty()) {
+        return Ok(None);
+    }
+
+    // 以字數最多的樣式為基本樣式，不加標籤
+    let mut styles: Vec<Style> = Vec::new();
+    let mut counts: Vec<usize> = Vec::new();
+    for (style, text) in &texts {
+        let count = text.chars().count();
+        match styles.iter().position(|s| s == *style) {
+            Some(i) => counts[i] += count,
+            None => {
+                styles.push((*style).clone());
+                counts.push(count);
+            }
+        }
+    }
+    let base = (0..styles.len())
+        .max_by_key(|i| (counts[*i], usize::MAX - i))
+        .unwrap();
+    let base_style = styles.remove(base);
+    styles.insert(0, base_style);
+
+    let mut source = String::new();
+    let mut objects = Vec::new();
+    for run in runs {
+        match run {
+            Run::Text { style, text } => {
+                let text = text.replace('\n', "<br/>");
+                match styles.iter().position(|s| *s == style).unwrap() {
+                    0 => source.push_str(&text),
+                    i => source.push_str(&format!("<s{i}>{text}</s{i}>")),
+                }
+            }
+            Run::Object(raw) => {
+                objects.push(raw);
+                source.push_str(&format!("<x{}/>", objects.len()));
+            }
+        }
+    }
+    // 合併相鄰的同樣式片段
+    let source = ADJACENT_STYLES
+        .replace_all(&source, |caps: &regex::Captures| {
+            if caps[1] == caps[2] {
+                String::new()
+            } else {
+                caps[0].to_string()
+            }
+        })
+        .into_owned();
+
+    Ok(Some(Paragraph {
+        open,
+        props,
+        styles,
+        objects,
+        source,
+    }))
+}
+
+fn parse_document(xml: &str) -> Result<Vec<Part>> {
+    let mut reader = Reader::from_str(xml);
+    let mut parts = Vec::new();
+    let mut raw_start = 0;
+    let mut depth = 0;
+    let mut paragraph_start = 0;
+    loop {
+        let start = position(&reader);
+        match reader.read_event()? {
+            Event::Start(e) if e.name().as_ref() == b"w:p" => {
+                if depth == 0 {
+                    paragraph_start = start;
+                }
+                depth += 1;
+            }
+            Event::End(e) if e.name().as_ref() == b"w:p" => {
+                depth -= 1;
+                let end = position(&reader);
+                if depth == 0
+                    && let Some(paragraph) = parse_paragraph(&xml[paragraph_start..end])?
+                {
+                    parts.push(Part::Raw(xml[raw_start..paragraph_start].to_string()));
+                    parts.push(Part::Paragraph(paragraph));
+                    raw_start = end;
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    parts.push(Part::Raw(xml[raw_start..].to_string()));
+    Ok(parts)
+}
+
+fn text_run(style: &Style, text: &str) -> String {
+    let content = text
+        .split('\t')
+        .map(|piece| {
+            if piece.is_empty() {
+                String::new()
+            } else {
+                format!("<w:t xml:space=\"preserve\">{}</w:t>", escape(piece))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("<w:tab/>");
+    style.wrap(&format!("<w:r>{}{}</w:r>", style.rpr, content))
+}
+
+impl Paragraph {
+    // 依標籤把譯文切成 run；未知的樣式編號視為基本樣式，遺漏的物件放在段落最後
+    fn render(&self, translation: &str) -> String {
+        let mut runs = String::new();
+        let mut used = vec![false; self.objects.len()];
+        let mut style = 0;
+        let mut last = 0;
+        for caps in TAG.captures_iter(translation) {
+            let tag = caps.get(0).unwrap();
+            if tag.start() > last {
+                runs.push_str(&text_run(
+                    &self.styles[style],
+                    &translation[last..tag.start()],
+                ));
+            }
+            last = tag.end();
+            if let Some(n) = caps.get(2) {
+                let index = n.as_str().parse::<usize>().unwrap_or(0);
+                if (1..=self.objects.len()).contains(&index) && !used[index - 1] {
+                    used[index - 1] = true;
+                    runs.push_str(&self.objects[index - 1]);
+                }
+            } else if tag.as_str().starts_with("<br") {
+                let style = &self.styles[style];
+                runs.push_str(&style.wrap(&format!("<w:r>{}<w:br/></w:r>", style.rpr)));
+            } else {
+                style = caps
+                    .get(1)
+                    .and_then(|n| n.as_str().parse().ok())
+                    .filter(|n| *n < self.styles.len())
+                    .unwrap_or(0);
+            }
+        }
+        if last < translation.len() {
+            runs.push_str(&text_run(&self.styles[style], &translation[last..]));
+        }
+        for (object, used) in self.objects.iter().zip(used) {
+            if !used {
+                runs.push_str(object);
+            }
+        }
+        format!("{}{}{}</w:p>", self.open, self.props, runs)
+    }
+}
+
+impl Docx {
+    pub fn parse(package: Vec<u8>) -> Result<Self> {
+        let mut archive = ZipArchive::new(Cursor::new(&package)).context("無法開啟 .docx")?;
+        let mut xml = String::new();
+        match archive.by_name(DOCUMENT_XML) {
+            Ok(mut file) => file.read_to_string(&mut xml)?,
+            Err(_) => bail!(".docx 中找不到 {}", DOCUMENT_XML),
+        };
+        let parts = parse_document(&xml).context("無法解析 .docx 的內容")?;
+        Ok(Self { package, parts })
+    }
+
+    /// 每個段落加上樣式標籤後的原文
+    pub fn paragraphs(&self) -> Vec<&str> {
+        self.parts
+            .iter()
+            .filter_map(|part| match part {
+                Part::Paragraph(p) => Some(p.source.as_str()),
+                Part::Raw(_) => None,
+            })
+            .collect()
+    }
+
+    /// 以譯文 (與 paragraphs 一一對應) 取代段落文字，並在文件最後加上 appendix 段落，產生新的 .docx
+    pub fn render(&self, translations: &[String], appendix: &[String]) -> Result<Vec<u8>> {
+        let mut translations = translations.iter();
+        let mut xml: String = self
+            .parts
+            .iter()
+            .map(|part| match part {
+                Part::Raw(raw) => raw.clone(),
+                Part::Paragraph(p) => p.render(translations.next().map_or("", |t| t.as_str())),
+            })
+            .collect();
+        if !appendix.is_empty() {
+            let paragraphs: String = appendix
+                .iter()
+                .map(|text| format!("<w:p>{}</w:p>", text_run(&Style::default(), text)))
+                .collect();
+            // 放在本文最後的節設定 (w:sectPr) 之前
+            let at = xml
+                .rfind("<w:sectPr")
+                .or_else(|| xml.rfind("</w:body>"))
+                .unwrap_or(xml.len());
+            xml.insert_str(at, &paragraphs);
+        }
+
+        let mut archive = ZipArchive::new(Cursor::new(&self.package))?;
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for i in 0..archive.len() {
+            let file = archive.by_index_raw(i)?;
+            if file.name() == DOCUMENT_XML {
+                continue;
+            }
+            writer.raw_copy_file(file)?;
+        }
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        writer.start_file(DOCUMENT_XML, options)?;
+        writer.write_all(xml.as_bytes())?;
+        Ok(writer.finish()?.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Docx;
+    use std::io::{Cursor, Read, Write};
+    use zip::write::SimpleFileOptions;
+    use zip::{ZipArchive, ZipWriter};
+
+    fn package(body: &str) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        writer.start_file("[Content_Types].xml", options).unwrap();
+        writer.write_all(b"<Types/>").unwrap();
+        writer.start_file("word/document.xml", options).unwrap();
+        let xml = format!(
+            "<?xml version=\"1.0\"?><w:document xmlns:w=\"w\"><w:body>{}</w:body></w:document>",
+            body
+        );
+        writer.write_all(xml.as_bytes()).unwrap();
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn document_xml(package: &[u8]) -> String {
+        let mut archive = ZipArchive::new(Cursor::new(package)).unwrap();
+        let mut xml = String::new();
+        archive
+            .by_name("word/document.xml")
+            .unwrap()
+            .read_to_string(&mut xml)
+            .unwrap();
+        xml
+    }
+
+    #[test]
+    fn run_styles_become_tags_and_are_restored() {
+        let body = "<w:p><w:pPr><w:jc w:val=\"center\"/></w:pPr><w:r><w:t>第一話</w:t></w:r></w:p>\
+            <w:p><w:r><w:t xml:space=\"preserve\">彼は </w:t></w:r>\
+            <w:r><w:rPr><w:i/></w:rPr><w:t>静かに</w:t></w:r>\
+            <w:r><w:rPr><w:i/></w:rPr><w:t>&amp;</w:t></w:r>\
+            <w:r><w:t>走った。</w:t><w:br/><w:t>終わり</w:t></w:r></w:p>\
+            <w:p><w:r><w:t>挿絵</w:t></w:r><w:r><w:drawing/></w:r></w:p>\
+            <w:p><w:r><w:ruby><w:rubyPr><w:hps w:val=\"10\"/></w:rubyPr>\
+            <w:rt><w:r><w:rPr><w:sz w:val=\"10\"/></w:rPr><w:t>つるぎ</w:t></w:r></w:rt>\
+            <w:rubyBase><w:r><w:t>剣</w:t></w:r></w:rubyBase></w:ruby></w:r></w:p>\
+            <w:p><w:r><w:drawing/></w:r></w:p><w:sectPr/>";
+        let docx = Docx::parse(package(body)).unwrap();
+        assert_eq!(
+            docx.paragraphs(),
+            [
+                "第一話",
+                "彼は <s1>静かに&</s1>走った。<br/>終わり",
+                "挿絵<x1/>",
+                "<ruby>剣<rt>つるぎ</rt></ruby>"
+            ]
+        );
+
+        let translations = ["第一話", "他<s1>靜靜地</s1>跑了。<br/>完", "插圖", "劍"];
+        let output = docx
+            .render(&translations.map(str::to_string), &["譯註".to_string()])
+            .unwrap();
+        let xml = document_xml(&output);
+        assert!(xml.contains("<w:pPr><w:jc w:val=\"center\"/></w:pPr>"));
+        assert!(
+            xml.contains(
+                "<w:r><w:rPr><w:i/></w:rPr><w:t xml:space=\"preserve\">靜靜地</w:t></w:r>"
+            )
+        );
+        assert!(xml.contains("<w:r><w:br/></w:r><w:r><w:t xml:space=\"preserve\">完</w:t></w:r>"));
+        assert!(xml.contains("插圖</w:t></w:r><w:r><w:drawing/></w:r></w:p>"));
+        assert!(xml.contains(
+            "<w:p><w:r><w:drawing/></w:r></w:p><w:p><w:r><w:t xml:space=\"preserve\">譯註</w:t></w:r></w:p><w:sectPr/>"
+        ));
+        // 其他檔案原樣保留，輸出仍可再次解析
+        assert_eq!(
+            Docx::parse(output).unwrap().paragraphs()[1],
+            "他<s1>靜靜地</s1>跑了。<br/>完"
+        );
+    }
+
+    #[test]
+    fn hyperlinks_bookmarks_and_insertions_are_kept() {
+        let body = "<w:p><w:bookmarkStart w:id=\"0\" w:name=\"top\"/><w:proofErr w:type=\"spellStart\"/>\
+            <w:r><w:t xml:space=\"preserve\">詳しくは</w:t></w:r>\
+            <w:hyperlink r:id=\"rId5\"><w:r><w:rPr><w:rStyle w:val=\"Hyperlink\"/></w:rPr><w:t>公式サイト</w:t></w:r></w:hyperlink>\
+            <w:ins w:id=\"1\" w:author=\"A\"><w:r><w:t>を見て</w:t></w:r></w:ins>\
+            <w:del w:id=\"2\" w:author=\"A\"><w:r><w:delText>ね</w:delText></w:r></w:del>\
+            <w:r><w:t>ください。</w:t></w:r><w:bookmarkEnd w:id=\"0\"/></w:p>";
+        let docx = Docx::parse(package(body)).unwrap();
+        assert_eq!(
+            docx.paragraphs(),
+            ["<x1/>詳しくは<s1>公式サイト</s1><s2>を見て</s2><x2/>ください。<x3/>"]
+        );
+
+        let translation = "<x1/>詳情請<s2>參閱</s2><s1>官方網站</s1><x2/>。<x3/>".to_string();
+        let xml = document_xml(&docx.render(&[translation], &[]).unwrap());
+        assert!(xml.contains("<w:p><w:bookmarkStart w:id=\"0\" w:name=\"top\"/><w:r>"));
+        assert!(xml.contains(
+            "<w:ins w:id=\"1\" w:author=\"A\"><w:r><w:t xml:space=\"preserve\">參閱</w:t></w:r></w:ins>"
+        ));
+        assert!(xml.contains(
+            "<w:hyperlink r:id=\"rId5\"><w:r><w:rPr><w:rStyle w:val=\"Hyperlink\"/></w:rPr>\
+             <w:t xml:space=\"preserve\">官方網站</w:t></w:r></w:hyperlink>"
+        ));
+        assert!(xml.contains(
+            "<w:del w:id=\"2\" w:author=\"A\"><w:r><w:delText>ね</w:delText></w:r></w:del>"
+        ));
+        assert!(xml.contains("<w:bookmarkEnd w:id=\"0\"/></w:p>"));
+        assert!(!xml.contains("proofErr"));
+    }
+}
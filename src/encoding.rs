@@ -0,0 +1,251 @@
+// src/encoding.rs
+
+use anyhow::{Context, Result, bail};
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8, UTF_16BE, UTF_16LE};
+use serde::Deserialize;
+use std::path::Path;
+
+fn default_input() -> String {
+    "auto".to_string()
+}
+
+fn default_output() -> String {
+    "utf-8".to_string()
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+// encoding 區塊：讀取時一律轉成 UTF-8 與 LF，寫入時再轉成指定的編碼與換行
+#[derive(Debug, Deserialize)]
+pub struct EncodingConfig {
+    #[serde(default = "default_input")]
+    pub input: String, // "auto" 或編碼名稱 (shift_jis, euc-jp, gbk, big5, utf-16le ...)
+    #[serde(default = "default_output")]
+    pub output: String, // utf-8, utf-8-bom, utf-16le, utf-16be 或其他編碼名稱
+    #[serde(default)]
+    pub line_endings: LineEnding,
+}
+
+impl Default for EncodingConfig {
+    fn default() -> Self {
+        Self {
+            input: default_input(),
+            output: default_output(),
+            line_endings: LineEnding::default(),
+        }
+    }
+}
+
+fn lookup(label: &str) -> Result<&'static Encoding> {
+    match Encoding::for_label(label.trim().as_bytes()) {
+        Some(encoding) => Ok(encoding),
+        None => bail!("未知的編碼: {}", label),
+    }
+}
+
+// 沒有 BOM 的 UTF-16：ASCII 字元的高位元組為 0，集中在奇數或偶數位置
+fn sniff_utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+    let sample = &bytes[..bytes.len().min(4096) & !1];
+    if sample.len() < 4 {
+        return None;
+    }
+    let half = (sample.len() / 2) as f64;
+    let zeros_at = |offset: usize| {
+        sample
+            .iter()
+            .skip(offset)
+            .step_by(2)
+            .filter(|b| **b == 0)
+            .count() as f64
+            / half
+    };
+    let (even, odd) = (zeros_at(0), zeros_at(1));
+    if odd > 0.3 && even < 0.05 {
+        Some(UTF_16LE)
+    } else if even > 0.3 && odd < 0.05 {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// 偵測編碼：BOM 優先，其次為設定值；auto 時依序嘗試 UTF-16、UTF-8 與 chardetng 的推測
+pub fn detect(bytes: &[u8], input: &str) -> Result<(&'static Encoding, usize)> {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+        return Ok((encoding, bom_len));
+    }
+    if !input.trim().eq_ignore_ascii_case("auto") {
+        return Ok((lookup(input)?, 0));
+    }
+    // 純 ASCII 的 UTF-16 也是合法的 UTF-8 (含大量 NUL)，因此先檢查 UTF-16
+    if let Some(encoding) = sniff_utf16(bytes) {
+        return Ok((encoding, 0));
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+        return Ok((UTF_8, 0));
+    }
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    Ok((detector.guess(None, true), 0))
+}
+
+/// 解碼為 UTF-8 並將換行統一為 LF
+pub fn decode(bytes: &[u8], input: &str) -> Result<(String, &'static Encoding)> {
+    let (encoding, bom_len) = detect(bytes, input)?;
+    let (text, had_errors) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+    if had_errors {
+        println!(
+            "[警告] 以 {} 解碼時有無法辨識的位元組，已替換為 U+FFFD；可在 encoding.input 指定正確的編碼",
+            encoding.name()
+        );
+    }
+    Ok((text.replace("\r\n", "\n").replace('\r', "\n"), encoding))
+}
+
+/// 依設定的編碼與換行轉成要寫入的位元組
+pub fn encode(text: &str, config: &EncodingConfig) -> Result<Vec<u8>> {
+    let text = match config.line_endings {
+        LineEnding::Lf => text.to_string(),
+        LineEnding::Crlf => text.replace("\r\n", "\n").replace('\n', "\r\n"),
+    };
+    let label = config.output.trim().to_lowercase();
+    // encoding_rs 不支援輸出 UTF-16，自行轉換並加上 BOM
+    let utf16 = |to_bytes: fn(u16) -> [u8; 2]| -> Vec<u8> {
+        std::iter::once(0xFEFF)
+            .chain(text.encode_utf16())
+            .flat_map(to_bytes)
+            .collect()
+    };
+    Ok(match label.as_str() {
+        "utf-8-bom" => [&[0xEF, 0xBB, 0xBF][..], text.as_bytes()].concat(),
+        "utf-16" | "utf-16le" => utf16(u16::to_le_bytes),
+        "utf-16be" => utf16(u16::to_be_bytes),
+        _ => {
+            let (bytes, _, unmappable) = lookup(&label)?.encode(&text);
+            if unmappable {
+                println!(
+                    "[警告] 部分字元無法以 {} 表示，已轉為 HTML 數值參照 (&#...;)",
+                    config.output
+                );
+            }
+            bytes.into_owned()
+        }
+    })
+}
+
+impl EncodingConfig {
+    /// 讀取原文 (依 input 設定偵測編碼)
+    pub fn read_input(&self, path: &Path) -> Result<String> {
+        Ok(decode(&fs_err::read(path)?, &self.input)?.0)
+    }
+
+    /// 讀取本程式寫出的譯文 (依 output 設定的編碼)
+    pub fn read_output(&self, path: &Path) -> Result<String> {
+        let label = match self.output.trim().to_lowercase().as_str() {
+            "utf-8-bom" | "utf-16" | "utf-16le" | "utf-16be" => "auto".to_string(), // 皆有 BOM
+            other => other.to_string(),
+        };
+        Ok(decode(&fs_err::read(path)?, &label)?.0)
+    }
+
+    /// 啟動時檢查編碼名稱，避免翻譯完才在寫檔時失敗
+    pub fn validate(&self) -> Result<()> {
+        if !self.input.trim().eq_ignore_ascii_case("auto") {
+            lookup(&self.input).context("encoding.input 設定錯誤")?;
+        }
+        encode("", self).context("encoding.output 設定錯誤")?;
+        Ok(())
+    }
+
+    /// 輸出 HTML 的 <meta charset> 值
+    pub fn html_charset(&self) -> String {
+        match self.output.trim().to_lowercase().as_str() {
+            "utf-8-bom" => "utf-8".to_string(),
+            "utf-16" | "utf-16le" | "utf-16be" => "utf-16".to_string(),
+            label => lookup(label)
+                .map(|encoding| encoding.name().to_lowercase())
+                .unwrap_or_else(|_| "utf-8".to_string()),
+        }
+    }
+
+    pub fn write_output(&self, path: &Path, text: &str) -> Result<()> {
+        fs_err::write(path, encode(text, self)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EncodingConfig, LineEnding, decode, encode};
+
+    #[test]
+    fn legacy_encodings_are_detected() {
+        let text = "吾輩は猫である。名前はまだ無い。どこで生れたかとんと見当がつかぬ。";
+        for encoding in [encoding_rs::SHIFT_JIS, encoding_rs::EUC_JP] {
+            let (bytes, _, _) = encoding.encode(text);
+            let (decoded, detected) = decode(&bytes, "auto").unwrap();
+            assert_eq!(decoded, text, "{}", encoding.name());
+            assert_eq!(detected, encoding);
+        }
+
+        let text = "這是一個關於魔法師與騎士的故事，他們一起踏上了漫長的旅程。";
+        let (bytes, _, _) = encoding_rs::BIG5.encode(text);
+        assert_eq!(decode(&bytes, "auto").unwrap().0, text);
+        let text = "这是一个关于魔法师与骑士的故事，他们一起踏上了漫长的旅程。";
+        let (bytes, _, _) = encoding_rs::GBK.encode(text);
+        assert_eq!(decode(&bytes, "auto").unwrap().0, text);
+    }
+
+    #[test]
+    fn bom_and_line_endings_are_normalized() {
+        let (text, _) = decode(b"\xEF\xBB\xBFa\r\nb", "shift_jis").unwrap();
+        assert_eq!(text, "a\nb");
+        let utf16: Vec<u8> = "ab\r\n".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        assert_eq!(decode(&utf16, "auto").unwrap().0, "ab\n");
+    }
+
+    #[test]
+    fn output_encoding_and_line_endings_are_applied() {
+        let config = EncodingConfig {
+            output: "utf-16le".to_string(),
+            line_endings: LineEnding::Crlf,
+            ..Default::default()
+        };
+        let bytes = encode("a\nb", &config).unwrap();
+        assert_eq!(bytes, b"\xFF\xFEa\0\r\0\n\0b\0");
+        assert_eq!(decode(&bytes, "auto").unwrap().0, "a\nb");
+
+        let config = EncodingConfig {
+            output: "big5".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            encode("中文", &config).unwrap(),
+            encoding_rs::BIG5.encode("中文").0.into_owned()
+        );
+        let config = EncodingConfig {
+            output: "bogus".to_string(),
+            ..Default::default()
+        };
+        assert!(encode("x", &config).is_err());
+        assert!(config.validate().is_err());
+        let config = EncodingConfig {
+            output: "big-5".to_string(),
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+        let config = EncodingConfig {
+            output: "Big5".to_string(),
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+        assert_eq!(config.html_charset(), "big5");
+    }
+}
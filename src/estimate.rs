@@ -0,0 +1,181 @@
+// src/estimate.rs
+
+use crate::llm::{ModelPricing, TokenUsage};
+
+// 分析回應中，字典 JSON 的預估額外 token
+const ANALYSIS_OUTPUT_OVERHEAD: usize = 200;
+
+/// 粗略估算 token 數：CJK 字元約 1 token/字，其餘約 4 字元/token
+pub fn estimate_tokens(text: &str) -> usize {
+    let (cjk, other) = text.chars().fold((0usize, 0usize), |(cjk, other), c| {
+        if is_cjk(c) {
+            (cjk + 1, other)
+        } else {
+            (cjk, other + 1)
+        }
+    });
+    cjk + other.div_ceil(4)
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3000..=0x30FF    // CJK 標點、平假名、片假名
+        | 0x3400..=0x4DBF  // CJK 擴充 A
+        | 0x4E00..=0x9FFF  // CJK 統一表意文字
+        | 0xAC00..=0xD7AF  // 韓文音節
+        | 0xF900..=0xFAFF  // CJK 相容表意文字
+        | 0xFF00..=0xFFEF  // 全形字元
+    )
+}
+
+// 單一章節的估算結果
+#[derive(Debug, Clone)]
+pub struct ChapterEstimate {
+    pub chapter: String,
+    pub analysis_prompt: usize,
+    pub translation_prompts: Vec<usize>, // 每個目標語言一次 Pass 2
+    pub content: usize,
+    pub analysis_output: usize,
+    pub translation_output: usize, // 單一語言的譯文
+}
+
+impl ChapterEstimate {
+    pub fn new(
+        chapter: &str,
+        analysis_prompt: &str,
+        translation_prompts: &[String],
+        content: &str,
+        max_summary_length: usize,
+    ) -> Self {
+        let content_tokens = estimate_tokens(content);
+        Self {
+            chapter: chapter.to_string(),
+            analysis_prompt: estimate_tokens(analysis_prompt),
+            translation_prompts: translation_prompts
+                .iter()
+                .map(|prompt| estimate_tokens(prompt))
+                .collect(),
+            content: content_tokens,
+            analysis_output: max_summary_length + ANALYSIS_OUTPUT_OVERHEAD,
+            // 譯文長度以原文長度估計
+            translation_output: content_tokens,
+        }
+    }
+
+    pub fn system_prompts(&self) -> usize {
+        self.analysis_prompt + self.translation_prompts.iter().sum::<usize>()
+    }
+
+    pub fn outputs(&self) -> usize {
+        self.analysis_output + self.translation_output * self.translation_prompts.len()
+    }
+
+    pub fn usage(&self) -> TokenUsage {
+        let calls = 1 + self.translation_prompts.len();
+        TokenUsage {
+            prompt_tokens: (self.system_prompts() + calls * self.content) as u64,
+            completion_tokens: self.outputs() as u64,
+        }
+    }
+
+    /// 單次呼叫所需的最大 context (輸入 + 輸出)
+    pub fn peak_context(&self) -> usize {
+        let analysis = self.analysis_prompt + self.content + self.analysis_output;
+        let translation = self
+            .translation_prompts
+            .iter()
+            .max()
+            .copied()
+            .unwrap_or_default()
+            + self.content
+            + self.translation_output;
+        analysis.max(translation)
+    }
+
+    pub fn exceeds(&self, context_window: Option<usize>) -> bool {
+        context_window.is_some_and(|limit| self.peak_context() > limit)
+    }
+}
+
+pub fn print_report(
+    estimates: &[ChapterEstimate],
+    context_window: Option<usize>,
+    pricing: Option<ModelPricing>,
+) {
+    println!(
+        "{:<24} {:>10} {:>10} {:>10} {:>10}",
+        "章節", "系統提示", "正文", "預估輸出", "最大context"
+    );
+
+    let mut total = TokenUsage::default();
+    let mut oversized = Vec::new();
+    for estimate in estimates {
+        println!(
+            "{:<24} {:>10} {:>10} {:>10} {:>10}",
+            estimate.chapter,
+            estimate.system_prompts(),
+            estimate.content,
+            estimate.outputs(),
+            estimate.peak_context()
+        );
+        total += estimate.usage();
+        if estimate.exceeds(context_window) {
+            oversized.push(estimate);
+        }
+    }
+
+    println!(
+        "\n合計 {} 章：輸入約 {} tokens，輸出約 {} tokens (合計 {})",
+        estimates.len(),
+        total.prompt_tokens,
+        total.completion_tokens,
+        total.total()
+    );
+    if let Some(pricing) = pricing {
+        println!("估計費用: ${:.4}", pricing.cost(&total));
+    }
+
+    for estimate in oversized {
+        println!(
+            "[警告] {} 預估需要 {} tokens，超過 context_window ({})",
+            estimate.chapter,
+            estimate.peak_context(),
+            context_window.unwrap_or_default()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChapterEstimate, estimate_tokens};
+
+    #[test]
+    fn cjk_characters_count_as_one_token_each() {
+        assert_eq!(estimate_tokens("吾輩は猫である"), 7);
+    }
+
+    #[test]
+    fn latin_text_counts_four_characters_per_token() {
+        assert_eq!(estimate_tokens("abcdefgh"), 2);
+        assert_eq!(estimate_tokens("abcde"), 2);
+    }
+
+    #[test]
+    fn estimate_flags_chapters_over_context_window() {
+        let content = "字".repeat(3000);
+        let estimate =
+            ChapterEstimate::new("001", "prompt", &["prompt".to_string()], &content, 300);
+        assert_eq!(estimate.peak_context(), 6002);
+        assert!(estimate.exceeds(Some(4096)));
+        assert!(!estimate.exceeds(None));
+    }
+
+    #[test]
+    fn each_target_language_adds_a_translation_call() {
+        let content = "字".repeat(100);
+        let prompts = ["prompt".to_string(), "prompt".to_string()];
+        let estimate = ChapterEstimate::new("001", "prompt", &prompts, &content, 50);
+        assert_eq!(estimate.usage().prompt_tokens, 3 * 2 + 3 * 100);
+        assert_eq!(estimate.usage().completion_tokens, 250 + 2 * 100);
+    }
+}
@@ -0,0 +1,314 @@
+// src/import.rs
+
+use anyhow::{Context, Result, bail};
+use regex::Regex;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+use crate::encoding::EncodingConfig;
+
+// 標題必須是整行：編號後直接換行，或隔著空白、冒號、括號等接上不太長的章名，
+// 避免「第二回目は……」「第一話の……」這類內文被當成標題
+fn default_heading_patterns() -> Vec<String> {
+    [
+        r"^第[0-9０-９一二三四五六七八九十百千零〇两兩]+[章話话回節节](?:[\s:：・\-－—「『（(].{0,40})?$",
+        r"(?i)^chapter\s*[0-9]+(?:[\s:.\-–—].{0,60})?$",
+        r"^(?:プロローグ|エピローグ|序章|終章|序幕|尾聲|楔子)(?:[\s:：・\-－—「『（(].{0,40})?$",
+    ]
+    .iter()
+    .map(|p| p.to_string())
+    .collect()
+}
+
+fn default_length_target() -> usize {
+    5000
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitMode {
+    #[default]
+    Headings, // 依標題切分，找不到標題時改依長度
+    Length,
+}
+
+// import 區塊：將整本小說切分成章節檔
+#[derive(Debug, Deserialize)]
+pub struct ImportConfig {
+    #[serde(default)]
+    pub split_by: SplitMode,
+    #[serde(default = "default_heading_patterns")]
+    pub heading_patterns: Vec<String>, // 比對去除前後空白後的單行
+    #[serde(default = "default_length_target")]
+    pub length_target: usize, // 依長度切分時每章的目標字數
+}
+
+impl Default for ImportConfig {
+    fn default() -> Self {
+        Self {
+            split_by: SplitMode::default(),
+            heading_patterns: default_heading_patterns(),
+            length_target: default_length_target(),
+        }
+    }
+}
+
+/// 切分後的章節：id 為依順序編號的檔名，title 為標題行 (依長度切分時為空)
+#[derive(Debug, PartialEq)]
+pub struct ImportedChapter {
+    pub id: String,
+    pub title: String,
+    pub text: String,
+}
+
+// (標題, 內文) 依標題行切分；沒有任何標題時回傳 None
+fn split_by_headings(text: &str, patterns: &[Regex]) -> Option<Vec<(String, String)>> {
+    let mut sections: Vec<(String, String)> = vec![(String::new(), String::new())];
+    let mut found = false;
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if !trimmed.is_empty() && patterns.iter().any(|p| p.is_match(trimmed)) {
+            found = true;
+            sections.push((trimmed.to_string(), String::new()));
+        }
+        let (_, body) = sections.last_mut().unwrap();
+        body.push_str(line);
+        body.push('\n');
+    }
+    if !found {
+        return None;
+    }
+    // 第一個標題之前只有空白時捨棄
+    if sections[0].1.trim().is_empty() {
+        sections.remove(0);
+    }
+    Some(sections)
+}
+
+// 累積到目標字數後，在該行結尾切分 (與 text::paragraphs 相同，每個非空白行都是一個段落，
+// 段落之間沒有空行的書也能切分)
+fn split_by_length(text: &str, target: usize) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = 0;
+    for line in text.lines() {
+        let current = parts.last_mut().unwrap();
+        current.push_str(line);
+        current.push('\n');
+        chars += line.trim().chars().count();
+        if chars >= target {
+            parts.push(String::new());
+            chars = 0;
+        }
+    }
+    parts.retain(|part| !part.trim().is_empty());
+    parts
+}
+
+/// 將整本小說切分成章節
+pub fn split_book(text: &str, config: &ImportConfig) -> Result<Vec<ImportedChapter>> {
+    let patterns = config
+        .heading_patterns
+        .iter()
+        .map(|p| Regex::new(p).context(format!("無效的標題樣式: {}", p)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let sections = match config.split_by {
+        SplitMode::Headings => split_by_headings(text, &patterns),
+        SplitMode::Length => None,
+    }
+    .unwrap_or_else(|| {
+        if config.split_by == SplitMode::Headings {
+            println!(
+                "[警告] 找不到符合樣式的章節標題，改為每 {} 字切分",
+                config.length_target
+            );
+        }
+        split_by_length(text, config.length_target.max(1))
+            .into_iter()
+            .map(|body| (String::new(), body))
+            .collect()
+    });
+
+    // 以章節總數決定編號位數 (至少 3 位)，讓檔名排序與章節順序一致
+    let width = sections.len().to_string().len().max(3);
+    Ok(sections
+        .into_iter()
+        .enumerate()
+        .map(|(i, (title, body))| ImportedChapter {
+            id: format!("{:0width$}", i + 1, width = width),
+            title,
+            text: format!("{}\n", body.trim_matches('\n')),
+        })
+        .collect())
+}
+
+// input_folder 中編號不在這次匯入範圍內的章節檔 (例如先前匯入較長版本留下的 120.txt)
+fn leftover_chapter_files(
+    input_folder: &Path,
+    chapters: &[ImportedChapter],
+) -> Result<Vec<PathBuf>> {
+    if !input_folder.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut leftovers = Vec::new();
+    for entry in fs_err::read_dir(input_folder)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "txt") {
+            continue;
+        }
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let numbered = !stem.is_empty() && stem.chars().all(|c| c.is_ascii_digit());
+        if numbered && !chapters.iter().any(|c| c.id == stem) {
+            leftovers.push(path);
+        }
+    }
+    leftovers.sort();
+    Ok(leftovers)
+}
+
+/// 讀取整本小說 (自動偵測編碼) 並寫成 input_folder 下的 UTF-8 章節檔 (<id>.txt)；
+/// 既有檔案內容不同、或有這次匯入不會產生的章節檔時停止，不會覆寫任何檔案
+pub fn import_book(
+    book: &Path,
+    input_folder: &Path,
+    config: &ImportConfig,
+    encoding: &EncodingConfig,
+) -> Result<Vec<ImportedChapter>> {
+    let text = encoding.read_input(book)?;
+    let chapters = split_book(&text, config)?;
+    if chapters.is_empty() {
+        bail!("{} 沒有任何內容", book.display());
+    }
+
+    let leftovers = leftover_chapter_files(input_folder, &chapters)?;
+    if !leftovers.is_empty() {
+        let list: Vec<String> = leftovers
+            .iter()
+            .map(|path| format!("  {}", path.display()))
+            .collect();
+        bail!(
+            "input_folder 中有這次匯入不會產生的章節檔，會被當成本書的一部分翻譯，請先移除:\n{}",
+            list.join("\n")
+        );
+    }
+    for chapter in &chapters {
+        let path = input_folder.join(format!("{}.txt", chapter.id));
+        if path.exists() && fs_err::read_to_string(&path)? != chapter.text {
+            bail!(
+                "{} 已存在且內容不同，請先清空 input_folder 再匯入",
+                path.display()
+            );
+        }
+    }
+    fs_err::create_dir_all(input_folder)?;
+    for chapter in &chapters {
+        fs_err::write(
+            input_folder.join(format!("{}.txt", chapter.id)),
+            &chapter.text,
+        )?;
+    }
+    Ok(chapters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ImportConfig, SplitMode, import_book, split_book};
+    use crate::encoding::EncodingConfig;
+
+    #[test]
+    fn headings_split_chapters_and_keep_preface() {
+        let book =
+            "作者序\n\nプロローグ\n始まり\n\n第1話　出会い\n本文一\n\n第２話 旅立ち\n本文二\n";
+        let chapters = split_book(book, &ImportConfig::default()).unwrap();
+        let titles: Vec<_> = chapters.iter().map(|c| c.title.as_str()).collect();
+        assert_eq!(titles, ["", "プロローグ", "第1話　出会い", "第２話 旅立ち"]);
+        assert_eq!(chapters[2].id, "003");
+        assert_eq!(chapters[2].text, "第1話　出会い\n本文一\n");
+    }
+
+    #[test]
+    fn sentences_starting_like_headings_are_not_split() {
+        let book = "第一話 出会い\n第二回目は失敗しなかった。\n第一話の感想を聞かれた。\n序章を読み返す。\nChapter 1 was long, and the second one was even longer than anyone expected it to be at all.\n第二話「再会」\n本文\n";
+        let chapters = split_book(book, &ImportConfig::default()).unwrap();
+        let titles: Vec<_> = chapters.iter().map(|c| c.title.as_str()).collect();
+        assert_eq!(titles, ["第一話 出会い", "第二話「再会」"]);
+    }
+
+    #[test]
+    fn english_headings_are_case_insensitive() {
+        let book = "CHAPTER 1\nText.\nChapter 2: The Road\nMore.\n";
+        let chapters = split_book(book, &ImportConfig::default()).unwrap();
+        assert_eq!(chapters.len(), 2);
+        assert_eq!(chapters[1].title, "Chapter 2: The Road");
+    }
+
+    #[test]
+    fn length_split_breaks_at_line_ends() {
+        let config = ImportConfig {
+            split_by: SplitMode::Length,
+            length_target: 4,
+            ..Default::default()
+        };
+        let chapters = split_book("一二\n三四\n五\n\n六七八九\n\n十\n", &config).unwrap();
+        let texts: Vec<_> = chapters.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, ["一二\n三四\n", "五\n\n六七八九\n", "十\n"]);
+        assert!(chapters.iter().all(|c| c.title.is_empty()));
+    }
+
+    #[test]
+    fn books_without_headings_fall_back_to_length() {
+        let config = ImportConfig {
+            length_target: 2,
+            ..Default::default()
+        };
+        let chapters = split_book("甲乙\n\n丙丁\n", &config).unwrap();
+        assert_eq!(chapters.len(), 2);
+    }
+
+    #[test]
+    fn books_without_blank_lines_are_split_by_length() {
+        let config = ImportConfig {
+            split_by: SplitMode::Length,
+            length_target: 6,
+            ..Default::default()
+        };
+        let book = "彼は走った。\n彼女も走った。\n誰もいない。\n雨が降る。\n";
+        let chapters = split_book(book, &config).unwrap();
+        let texts: Vec<_> = chapters.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            [
+                "彼は走った。\n",
+                "彼女も走った。\n",
+                "誰もいない。\n",
+                "雨が降る。\n"
+            ]
+        );
+    }
+
+    #[test]
+    fn leftover_chapters_from_a_longer_import_are_reported() {
+        let folder = std::env::temp_dir().join(format!(
+            "ai-novel-translation-import-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&folder);
+        std::fs::create_dir_all(&folder).unwrap();
+        let book = folder.join("book.txt");
+        let input_folder = folder.join("input");
+        let encoding = EncodingConfig::default();
+
+        std::fs::write(&book, "第1話\n一\n第2話\n二\n第3話\n三\n").unwrap();
+        import_book(&book, &input_folder, &ImportConfig::default(), &encoding).unwrap();
+        std::fs::write(&book, "第1話\n一\n第2話\n二\n").unwrap();
+        let error = import_book(&book, &input_folder, &ImportConfig::default(), &encoding)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("003.txt"));
+        assert!(!error.contains("002.txt"));
+
+        std::fs::remove_file(input_folder.join("003.txt")).unwrap();
+        import_book(&book, &input_folder, &ImportConfig::default(), &encoding).unwrap();
+        let _ = std::fs::remove_dir_all(&folder);
+    }
+}
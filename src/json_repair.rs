@@ -0,0 +1,116 @@
+// src/json_repair.rs
+
+/// 從模型回應中取出最外層的 JSON 物件，並修正常見的格式錯誤：
+/// 前後多餘的說明文字、結尾多餘的逗號、字串內未跳脫的引號與換行
+pub fn repair_json_object(raw: &str) -> Option<String> {
+    let start = raw.find('{')?;
+    let end = raw.rfind('}')?;
+    if end < start {
+        return None;
+    }
+    Some(repair(&raw[start..=end]))
+}
+
+fn repair(json: &str) -> String {
+    let chars: Vec<char> = json.chars().collect();
+    let mut out = String::with_capacity(json.len());
+    let mut in_string = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if in_string {
+            match c {
+                '\\' => {
+                    out.push(c);
+                    if let Some(&next) = chars.get(i + 1) {
+                        out.push(next);
+                        i += 1;
+                    }
+                }
+                '"' if is_closing_quote(&chars, i) => {
+                    in_string = false;
+                    out.push(c);
+                }
+                '"' => out.push_str("\\\""),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\t' => out.push_str("\\t"),
+                _ => out.push(c),
+            }
+        } else {
+            match c {
+                '"' => {
+                    in_string = true;
+                    out.push(c);
+                }
+                // 結尾多餘的逗號：, } 或 , ]
+                ',' if matches!(next_non_whitespace(&chars, i + 1), Some((_, '}' | ']'))) => {}
+                _ => out.push(c),
+            }
+        }
+        i += 1;
+    }
+    out
+}
+
+// 判斷字串中的引號是結束引號，還是內容裡未跳脫的引號
+fn is_closing_quote(chars: &[char], index: usize) -> bool {
+    match next_non_whitespace(chars, index + 1) {
+        None => true,
+        Some((_, ':' | '}' | ']')) => true,
+        Some((pos, ',')) => matches!(
+            next_non_whitespace(chars, pos + 1),
+            None | Some((_, '"' | '}' | ']'))
+        ),
+        _ => false,
+    }
+}
+
+fn next_non_whitespace(chars: &[char], from: usize) -> Option<(usize, char)> {
+    chars
+        .iter()
+        .enumerate()
+        .skip(from)
+        .find(|(_, c)| !c.is_whitespace())
+        .map(|(i, c)| (i, *c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::repair_json_object;
+    use serde_json::Value;
+
+    fn repaired(raw: &str) -> Value {
+        serde_json::from_str(&repair_json_object(raw).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn leading_and_trailing_prose_is_dropped() {
+        let value = repaired("好的，以下是結果：\n{\"summary\": \"ok\"}\n希望有幫助！");
+        assert_eq!(value["summary"], "ok");
+    }
+
+    #[test]
+    fn trailing_commas_are_removed() {
+        let value = repaired("{\"summary\": \"ok\", \"new_glossary\": {\"A\": \"甲\",},}");
+        assert_eq!(value["new_glossary"]["A"], "甲");
+    }
+
+    #[test]
+    fn unescaped_quotes_in_summary_are_escaped() {
+        let value = repaired("{\"summary\": \"他說\"好\"，然後離開\", \"new_glossary\": {}}");
+        assert_eq!(value["summary"], "他說\"好\"，然後離開");
+    }
+
+    #[test]
+    fn raw_newlines_in_strings_are_escaped() {
+        let value = repaired("{\"summary\": \"第一行\n第二行\"}");
+        assert_eq!(value["summary"], "第一行\n第二行");
+    }
+
+    #[test]
+    fn missing_object_returns_none() {
+        assert!(repair_json_object("沒有 JSON").is_none());
+    }
+}
@@ -0,0 +1,350 @@
+// src/language.rs
+
+/// source_language 設為此值時，逐章依內文偵測語言
+pub const AUTO: &str = "auto";
+
+// 少於這個字數的文本不做判斷
+const MIN_LETTERS: usize = 20;
+
+/// 以 Unicode 文字區段統計可分辨的語言 (拉丁字母一律視為英文)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Japanese,
+    Korean,
+    Chinese,
+    English,
+    Russian,
+}
+
+const LANGUAGES: [Language; 5] = [
+    Language::Japanese,
+    Language::Korean,
+    Language::Chinese,
+    Language::English,
+    Language::Russian,
+];
+
+impl Language {
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::Japanese => "Japanese",
+            Language::Korean => "Korean",
+            Language::Chinese => "Chinese",
+            Language::English => "English",
+            Language::Russian => "Russian",
+        }
+    }
+
+    // (名稱中包含的關鍵字, 語言代碼)
+    fn aliases(self) -> (&'static [&'static str], &'static str) {
+        match self {
+            Language::Japanese => (&["japanese", "日文", "日語", "日本語"], "ja"),
+            Language::Korean => (&["korean", "韓文", "韓語", "한국어"], "ko"),
+            Language::Chinese => (
+                &["chinese", "中文", "華語", "漢語", "繁體", "简体", "簡體"],
+                "zh",
+            ),
+            Language::English => (&["english", "英文", "英語"], "en"),
+            Language::Russian => (&["russian", "俄文", "俄語"], "ru"),
+        }
+    }
+
+    /// 將設定中的語言名稱對應到可偵測的語言，例如 "Traditional Chinese (Taiwan)"、"ja"
+    pub fn from_name(name: &str) -> Option<Language> {
+        let name = name.trim().to_lowercase();
+        LANGUAGES.into_iter().find(|lang| {
+            let (keywords, code) = lang.aliases();
+            name == code
+                || name.starts_with(&format!("{}-", code))
+                || keywords.iter().any(|keyword| name.contains(keyword))
+        })
+    }
+}
+
+/// 中文的字體；簡轉繁 (或繁轉簡) 時原文與目標同屬中文，需要再分字體
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChineseScript {
+    Simplified,
+    Traditional,
+}
+
+// 常用字中簡繁寫法不同的字，兩組依序對應
+const SIMPLIFIED_ONLY: &str =
+    "们这说为国时对会来个后过还没么见开门问间让给话认样现发经进动边两种听头";
+const TRADITIONAL_ONLY: &str =
+    "們這說為國時對會來個後過還沒麼見開門問間讓給話認樣現發經進動邊兩種聽頭";
+
+// 語言名稱中表示字體的關鍵字
+const TRADITIONAL_NAMES: &[&str] = &[
+    "traditional",
+    "繁",
+    "hant",
+    "zh-tw",
+    "zh-hk",
+    "zh-mo",
+    "taiwan",
+    "hong kong",
+    "台灣",
+    "臺灣",
+    "香港",
+];
+const SIMPLIFIED_NAMES: &[&str] = &[
+    "simplified",
+    "简",
+    "簡",
+    "hans",
+    "zh-cn",
+    "zh-sg",
+    "mainland",
+    "大陆",
+    "大陸",
+];
+
+impl ChineseScript {
+    /// 由設定中的語言名稱判斷字體，例如 "Traditional Chinese (Taiwan)"、"zh-CN"；未指明時回傳 None
+    pub fn from_name(name: &str) -> Option<ChineseScript> {
+        let name = name.trim().to_lowercase();
+        let matches = |keywords: &[&str]| keywords.iter().any(|keyword| name.contains(keyword));
+        if matches(TRADITIONAL_NAMES) {
+            Some(ChineseScript::Traditional)
+        } else if matches(SIMPLIFIED_NAMES) {
+            Some(ChineseScript::Simplified)
+        } else {
+            None
+        }
+    }
+
+    /// 依簡繁寫法不同的常用字判斷字體，兩者都沒出現或數量相同時回傳 None
+    pub fn detect(text: &str) -> Option<ChineseScript> {
+        let (mut simplified, mut traditional) = (0usize, 0usize);
+        for c in text.chars() {
+            if SIMPLIFIED_ONLY.contains(c) {
+                simplified += 1;
+            } else if TRADITIONAL_ONLY.contains(c) {
+                traditional += 1;
+            }
+        }
+        match simplified.cmp(&traditional) {
+            std::cmp::Ordering::Greater => Some(ChineseScript::Simplified),
+            std::cmp::Ordering::Less => Some(ChineseScript::Traditional),
+            std::cmp::Ordering::Equal => None,
+        }
+    }
+}
+
+/// 依文字區段比例判斷語言；字數太少或無法判斷時回傳 None
+pub fn detect(text: &str) -> Option<Language> {
+    let (mut kana, mut hangul, mut han, mut latin, mut cyrillic) = (0usize, 0, 0, 0, 0);
+    for c in text.chars() {
+        match c {
+            '\u{3040}'..='\u{30ff}' | '\u{31f0}'..='\u{31ff}' | '\u{ff66}'..='\u{ff9d}' => {
+                kana += 1
+            }
+            '\u{1100}'..='\u{11ff}' | '\u{3130}'..='\u{318f}' | '\u{ac00}'..='\u{d7af}' => {
+                hangul += 1
+            }
+            '\u{3400}'..='\u{4dbf}' | '\u{4e00}'..='\u{9fff}' | '\u{f900}'..='\u{faff}' => han += 1,
+            'a'..='z' | 'A'..='Z' | '\u{00c0}'..='\u{024f}' => latin += 1,
+            '\u{0400}'..='\u{04ff}' => cyrillic += 1,
+            _ => {}
+        }
+    }
+
+    let total = kana + hangul + han + latin + cyrillic;
+    if total < MIN_LETTERS {
+        return None;
+    }
+    // 日文漢字比例可能很高，只要假名佔一定比例就視為日文
+    let ratio = |count: usize| count as f64 / total as f64;
+    if ratio(hangul) > 0.2 && hangul > kana {
+        Some(Language::Korean)
+    } else if ratio(kana) > 0.05 {
+        Some(Language::Japanese)
+    } else if ratio(han) > 0.3 {
+        Some(Language::Chinese)
+    } else if ratio(latin) > 0.5 {
+        Some(Language::English)
+    } else if ratio(cyrillic) > 0.5 {
+        Some(Language::Russian)
+    } else {
+        None
+    }
+}
+
+/// 是否為日文：以內文偵測為準，字數太少無法判斷時才看設定的語言
+pub fn is_japanese(configured: &str, text: &str) -> bool {
+    match detect(text) {
+        Some(lang) => lang == Language::Japanese,
+        None => Language::from_name(configured) == Some(Language::Japanese),
+    }
+}
+
+/// 本章的原文語言判斷結果
+#[derive(Debug, PartialEq)]
+pub struct SourceLanguage {
+    pub name: String, // 提供給模板的 source_lang，無法判斷時為空字串
+    pub warnings: Vec<String>,
+}
+
+/// 依設定決定本章的原文語言，並檢查內文是否與設定不符或已是某個目標語言
+pub fn resolve_source_language(configured: &str, targets: &[&str], text: &str) -> SourceLanguage {
+    let detected = detect(text);
+    let auto = configured.trim().eq_ignore_ascii_case(AUTO);
+    let mut warnings = Vec::new();
+
+    let name = if auto {
+        match detected {
+            Some(lang) => lang.name().to_string(),
+            None => {
+                warnings.push("無法偵測原文語言，source_lang 將為空白".to_string());
+                String::new()
+            }
+        }
+    } else {
+        if let (Some(expected), Some(lang)) = (Language::from_name(configured), detected)
+            && expected != lang
+        {
+            warnings.push(format!(
+                "偵測到的語言為 {}，與設定的 source_language ({}) 不符",
+                lang.name(),
+                configured
+            ));
+        }
+        configured.to_string()
+    };
+
+    // 多個目標語言時逐一檢查，例如中文原文在 English 目標不提醒、在中文目標才提醒
+    if let Some(lang) = detected {
+        let configured = if auto { "" } else { configured };
+        for target in targets {
+            if Language::from_name(target) == Some(lang)
+                && !different_chinese_script(lang, configured, target, text)
+            {
+                warnings.push(format!(
+                    "內文看起來已是目標語言 {} ({})，可能是已翻譯過的章節",
+                    target,
+                    lang.name()
+                ));
+            }
+        }
+    }
+
+    SourceLanguage { name, warnings }
+}
+
+// 同為中文但字體不同 (例如簡轉繁) 時不算已是目標語言；內文判斷不出字體時改看設定的原文語言
+fn different_chinese_script(lang: Language, configured: &str, target: &str, text: &str) -> bool {
+    if lang != Language::Chinese {
+        return false;
+    }
+    let source = ChineseScript::detect(text).or_else(|| ChineseScript::from_name(configured));
+    matches!(
+        (source, ChineseScript::from_name(target)),
+        (Some(source), Some(target)) if source != target
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChineseScript, Language, detect, resolve_source_language};
+
+    const JAPANESE: &str =
+        "彼女は静かに扉を開けて、誰もいない教室の中へ入っていった。窓の外では雨が降っている。";
+    const KOREAN: &str =
+        "그녀는 조용히 문을 열고 아무도 없는 교실 안으로 들어갔다. 창밖에는 비가 내리고 있었다.";
+    const CHINESE: &str = "她靜靜地打開門，走進空無一人的教室。窗外正下著雨，天色越來越暗了。";
+    const SIMPLIFIED: &str = "她静静地打开门，走进空无一人的教室。窗外正下着雨，天色越来越暗了。";
+    const ENGLISH: &str =
+        "She quietly opened the door and walked into the empty classroom while it rained.";
+
+    #[test]
+    fn scripts_are_detected() {
+        assert_eq!(detect(JAPANESE), Some(Language::Japanese));
+        assert_eq!(detect(KOREAN), Some(Language::Korean));
+        assert_eq!(detect(CHINESE), Some(Language::Chinese));
+        assert_eq!(detect(ENGLISH), Some(Language::English));
+        assert_eq!(detect("短い"), None);
+    }
+
+    #[test]
+    fn config_names_map_to_languages() {
+        assert_eq!(
+            Language::from_name("Traditional Chinese (Taiwan)"),
+            Some(Language::Chinese)
+        );
+        assert_eq!(Language::from_name("zh-TW"), Some(Language::Chinese));
+        assert_eq!(Language::from_name("日本語"), Some(Language::Japanese));
+        assert_eq!(Language::from_name("Klingon"), None);
+    }
+
+    #[test]
+    fn auto_uses_detected_language() {
+        let result = resolve_source_language("auto", &["Traditional Chinese (Taiwan)"], JAPANESE);
+        assert_eq!(result.name, "Japanese");
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn mismatches_are_reported() {
+        let result = resolve_source_language("Japanese", &["Traditional Chinese (Taiwan)"], KOREAN);
+        assert_eq!(result.name, "Japanese");
+        assert_eq!(result.warnings.len(), 1);
+
+        let result = resolve_source_language("auto", &["Traditional Chinese (Taiwan)"], CHINESE);
+        assert_eq!(result.name, "Chinese");
+        assert!(result.warnings[0].contains("目標語言"));
+    }
+
+    #[test]
+    fn simplified_to_traditional_is_not_reported_as_translated() {
+        assert_eq!(
+            ChineseScript::detect(SIMPLIFIED),
+            Some(ChineseScript::Simplified)
+        );
+        assert_eq!(
+            ChineseScript::detect(CHINESE),
+            Some(ChineseScript::Traditional)
+        );
+        assert_eq!(
+            ChineseScript::from_name("zh-Hans"),
+            Some(ChineseScript::Simplified)
+        );
+
+        let target = ["Traditional Chinese (Taiwan)"];
+        assert!(
+            resolve_source_language("auto", &target, SIMPLIFIED)
+                .warnings
+                .is_empty()
+        );
+        assert!(
+            resolve_source_language("Simplified Chinese", &target, SIMPLIFIED)
+                .warnings
+                .is_empty()
+        );
+        // 已經是繁體的章節仍要提醒
+        let result = resolve_source_language("Simplified Chinese", &target, CHINESE);
+        assert!(result.warnings[0].contains("目標語言"));
+    }
+
+    #[test]
+    fn each_target_is_checked_for_already_translated_text() {
+        let targets = [
+            "English",
+            "Traditional Chinese (Taiwan)",
+            "Simplified Chinese",
+        ];
+        let result = resolve_source_language("auto", &targets, CHINESE);
+        assert_eq!(result.warnings.len(), 1);
+        assert!(result.warnings[0].contains("Traditional Chinese (Taiwan)"));
+
+        let result = resolve_source_language("auto", &targets, ENGLISH);
+        assert_eq!(result.warnings.len(), 1);
+        assert!(result.warnings[0].contains("English"));
+
+        assert!(
+            resolve_source_language("auto", &targets, JAPANESE)
+                .warnings
+                .is_empty()
+        );
+    }
+}
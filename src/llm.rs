@@ -3,8 +3,10 @@
 use anyhow::{Context, Result, bail};
 use async_trait::async_trait;
 use reqwest::Client;
-use serde::Deserialize; // 如果需要 Serialize 也要加
+use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::collections::HashMap;
+use std::ops::AddAssign;
 
 // --- 1. LLM 相關的設定結構 (搬移至此並設為 pub) ---
 
@@ -14,6 +16,92 @@ pub struct LlmConfig {
     pub gemini: Option<GeminiConfig>,
     pub ollama: Option<OllamaConfig>,
     pub openai: Option<OpenAIConfig>,
+    #[serde(default)]
+    pub pricing: HashMap<String, ModelPricing>, // key 為模型名稱
+    #[serde(default = "default_structured_output")]
+    pub structured_output: bool, // false: 只要求 JSON，不傳送 schema (相容不支援的服務)
+}
+
+fn default_structured_output() -> bool {
+    true
+}
+
+/// 每次請求固定的 temperature
+const TEMPERATURE: f64 = 0.2;
+/// Ollama 的 context 長度
+const OLLAMA_NUM_CTX: u64 = 4096;
+const GEMINI_ENDPOINT: &str = "https://generativelanguage.googleapis.com/v1beta";
+const OPENAI_ENDPOINT: &str = "https://api.openai.com/v1";
+
+// 各 provider 請求中的生成參數
+fn generation_options(provider: &str) -> serde_json::Value {
+    match provider {
+        "ollama" => json!({ "temperature": TEMPERATURE, "num_ctx": OLLAMA_NUM_CTX }),
+        _ => json!({ "temperature": TEMPERATURE }),
+    }
+}
+
+impl LlmConfig {
+    /// 目前 provider 所使用的模型名稱
+    pub fn active_model(&self) -> Option<&str> {
+        match self.provider.as_str() {
+            "gemini" => self.gemini.as_ref().map(|c| c.model.as_str()),
+            "ollama" => self.ollama.as_ref().map(|c| c.model.as_str()),
+            "openai" => self.openai.as_ref().map(|c| c.model.as_str()),
+            _ => None,
+        }
+    }
+
+    /// 目前 provider 的服務位址
+    pub fn active_endpoint(&self) -> Option<&str> {
+        match self.provider.as_str() {
+            "gemini" => Some(GEMINI_ENDPOINT),
+            "ollama" => self
+                .ollama
+                .as_ref()
+                .map(|c| c.base_url.trim_end_matches('/')),
+            "openai" => self.openai.as_ref().map(|c| {
+                c.base_url
+                    .as_deref()
+                    .unwrap_or(OPENAI_ENDPOINT)
+                    .trim_end_matches('/')
+            }),
+            _ => None,
+        }
+    }
+
+    /// 影響回應內容的請求參數 (provider、模型、服務位址與生成參數)，用於快取 key
+    pub fn request_signature(&self) -> String {
+        format!(
+            "{}\n{}\n{}\n{}",
+            self.provider,
+            self.active_model().unwrap_or_default(),
+            self.active_endpoint().unwrap_or_default(),
+            generation_options(&self.provider)
+        )
+    }
+
+    /// 目前模型的單價設定 (未設定則無法估算費用)
+    pub fn active_pricing(&self) -> Option<ModelPricing> {
+        self.active_model()
+            .and_then(|model| self.pricing.get(model))
+            .copied()
+    }
+}
+
+/// 每百萬 token 的價格 (幣別由使用者自行決定，通常為 USD)
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub struct ModelPricing {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+impl ModelPricing {
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        (usage.prompt_tokens as f64 * self.input_per_million
+            + usage.completion_tokens as f64 * self.output_per_million)
+            / 1_000_000.0
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -37,15 +125,85 @@ pub struct OpenAIConfig {
 
 // --- 2. 定義 Trait ---
 
+/// 單次呼叫的 token 用量 (provider 未回報時為 0)
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl TokenUsage {
+    pub fn total(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+impl AddAssign for TokenUsage {
+    fn add_assign(&mut self, other: Self) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+    }
+}
+
+/// 回應格式要求
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResponseFormat {
+    Text,
+    Json, // 只要求 JSON，不限制結構
+    JsonSchema {
+        name: &'static str,
+        schema: serde_json::Value,
+    },
+}
+
+impl ResponseFormat {
+    /// 用於快取 key 與稽核紀錄的描述
+    pub fn describe(&self) -> String {
+        match self {
+            ResponseFormat::Text => "text".to_string(),
+            ResponseFormat::Json => "json".to_string(),
+            ResponseFormat::JsonSchema { name, schema } => {
+                format!("json_schema:{}:{}", name, schema)
+            }
+        }
+    }
+}
+
+// Gemini 的 responseSchema 為 OpenAPI 子集，不接受 additionalProperties
+fn gemini_schema(schema: &serde_json::Value) -> serde_json::Value {
+    match schema {
+        serde_json::Value::Object(map) => map
+            .iter()
+            .filter(|(key, _)| key.as_str() != "additionalProperties")
+            .map(|(key, value)| (key.clone(), gemini_schema(value)))
+            .collect::<serde_json::Map<_, _>>()
+            .into(),
+        serde_json::Value::Array(items) => items.iter().map(gemini_schema).collect(),
+        other => other.clone(),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LlmResponse {
+    pub text: String,
+    pub usage: TokenUsage,
+    pub cached: bool, // 由快取取得，未實際呼叫 API
+}
+
 #[async_trait]
 pub trait LlmClient: Send + Sync {
-    /// json_mode: 用來告訴 LLM 是否強制輸出 JSON 格式
+    /// format: 要求 LLM 以純文字、JSON 或指定 JSON Schema 輸出
     async fn generate(
         &self,
         system_prompt: &str,
         user_content: &str,
-        json_mode: bool,
-    ) -> Result<String>;
+        format: &ResponseFormat,
+    ) -> Result<LlmResponse>;
+}
+
+// 從回應 JSON 讀取數值欄位，缺少時視為 0
+fn usage_field(value: &serde_json::Value) -> u64 {
+    value.as_u64().unwrap_or(0)
 }
 
 // --- 3. Gemini 實作 ---
@@ -61,23 +219,25 @@ impl LlmClient for GeminiClient {
         &self,
         system_prompt: &str,
         user_content: &str,
-        json_mode: bool,
-    ) -> Result<String> {
+        format: &ResponseFormat,
+    ) -> Result<LlmResponse> {
         let url = format!(
-            "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent?key={}",
-            self.config.model, self.config.api_key
+            "{}/models/{}:generateContent?key={}",
+            GEMINI_ENDPOINT, self.config.model, self.config.api_key
         );
 
-        let generation_config = if json_mode {
-            json!({
-                "temperature": 0.2,
-                "responseMimeType": "application/json"
-            })
-        } else {
-            json!({
-                "temperature": 0.2
-            })
-        };
+        let mut generation_config = generation_options("gemini");
+        let options = generation_config.as_object_mut().unwrap();
+        match format {
+            ResponseFormat::Text => {}
+            ResponseFormat::Json => {
+                options.insert("responseMimeType".to_string(), json!("application/json"));
+            }
+            ResponseFormat::JsonSchema { schema, .. } => {
+                options.insert("responseMimeType".to_string(), json!("application/json"));
+                options.insert("responseSchema".to_string(), gemini_schema(schema));
+            }
+        }
 
         let payload = json!({
             "system_instruction": {
@@ -102,7 +262,19 @@ impl LlmClient for GeminiClient {
             .context("無法解析 Gemini 回傳內容")?
             .to_string();
 
-        Ok(text)
+        // thoughtsTokenCount 同樣以輸出計費
+        let meta = &body["usageMetadata"];
+        let usage = TokenUsage {
+            prompt_tokens: usage_field(&meta["promptTokenCount"]),
+            completion_tokens: usage_field(&meta["candidatesTokenCount"])
+                + usage_field(&meta["thoughtsTokenCount"]),
+        };
+
+        Ok(LlmResponse {
+            text,
+            usage,
+            cached: false,
+        })
     }
 }
 
@@ -119,8 +291,8 @@ impl LlmClient for OllamaClient {
         &self,
         system_prompt: &str,
         user_content: &str,
-        json_mode: bool,
-    ) -> Result<String> {
+        format: &ResponseFormat,
+    ) -> Result<LlmResponse> {
         let url = format!("{}/api/chat", self.config.base_url.trim_end_matches('/'));
 
         let mut payload = json!({
@@ -130,17 +302,23 @@ impl LlmClient for OllamaClient {
                 { "role": "user", "content": user_content }
             ],
             "stream": false,
-            "options": {
-                "temperature": 0.2,
-                "num_ctx": 4096
-            }
+            "options": generation_options("ollama")
         });
 
-        if json_mode {
-            payload
-                .as_object_mut()
-                .unwrap()
-                .insert("format".to_string(), json!("json"));
+        match format {
+            ResponseFormat::Text => {}
+            ResponseFormat::Json => {
+                payload
+                    .as_object_mut()
+                    .unwrap()
+                    .insert("format".to_string(), json!("json"));
+            }
+            ResponseFormat::JsonSchema { schema, .. } => {
+                payload
+                    .as_object_mut()
+                    .unwrap()
+                    .insert("format".to_string(), schema.clone());
+            }
         }
 
         let res = self.client.post(&url).json(&payload).send().await?;
@@ -157,7 +335,16 @@ impl LlmClient for OllamaClient {
             .context("無法解析 Ollama 回傳內容")?
             .to_string();
 
-        Ok(text)
+        let usage = TokenUsage {
+            prompt_tokens: usage_field(&body["prompt_eval_count"]),
+            completion_tokens: usage_field(&body["eval_count"]),
+        };
+
+        Ok(LlmResponse {
+            text,
+            usage,
+            cached: false,
+        })
     }
 }
 
@@ -174,13 +361,9 @@ impl LlmClient for OpenAIClient {
         &self,
         system_prompt: &str,
         user_content: &str,
-        json_mode: bool,
-    ) -> Result<String> {
-        let base_url = self
-            .config
-            .base_url
-            .as_deref()
-            .unwrap_or("https://api.openai.com/v1");
+        format: &ResponseFormat,
+    ) -> Result<LlmResponse> {
+        let base_url = self.config.base_url.as_deref().unwrap_or(OPENAI_ENDPOINT);
         let url = format!("{}/chat/completions", base_url.trim_end_matches('/'));
 
         let mut payload = json!({
@@ -188,15 +371,30 @@ impl LlmClient for OpenAIClient {
             "messages": [
                 { "role": "system", "content": system_prompt },
                 { "role": "user", "content": user_content }
-            ],
-            "temperature": 0.2
+            ]
         });
-
-        if json_mode {
-            payload
-                .as_object_mut()
-                .unwrap()
-                .insert("response_format".to_string(), json!({ "type": "json_object" }));
+        payload
+            .as_object_mut()
+            .unwrap()
+            .extend(generation_options("openai").as_object().unwrap().clone());
+
+        match format {
+            ResponseFormat::Text => {}
+            ResponseFormat::Json => {
+                payload.as_object_mut().unwrap().insert(
+                    "response_format".to_string(),
+                    json!({ "type": "json_object" }),
+                );
+            }
+            ResponseFormat::JsonSchema { name, schema } => {
+                payload.as_object_mut().unwrap().insert(
+                    "response_format".to_string(),
+                    json!({
+                        "type": "json_schema",
+                        "json_schema": { "name": name, "strict": true, "schema": schema }
+                    }),
+                );
+            }
         }
 
         let res = self
@@ -218,7 +416,16 @@ impl LlmClient for OpenAIClient {
             .context("無法解析 OpenAI 回傳內容")?
             .to_string();
 
-        Ok(text)
+        let usage = TokenUsage {
+            prompt_tokens: usage_field(&body["usage"]["prompt_tokens"]),
+            completion_tokens: usage_field(&body["usage"]["completion_tokens"]),
+        };
+
+        Ok(LlmResponse {
+            text,
+            usage,
+            cached: false,
+        })
     }
 }
 
@@ -251,3 +458,68 @@ pub fn create_llm_client(config: &LlmConfig) -> Result<Box<dyn LlmClient>> {
         _ => bail!("未知的 LLM Provider: {}", config.provider),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{LlmConfig, ResponseFormat, gemini_schema};
+    use serde_json::json;
+
+    fn ollama_config(base_url: &str) -> LlmConfig {
+        serde_norway::from_str(&format!(
+            "provider: ollama\nollama:\n  base_url: \"{}\"\n  model: qwen\n",
+            base_url
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn request_signature_includes_endpoint_and_options() {
+        let local = ollama_config("http://localhost:11434/").request_signature();
+        assert_eq!(
+            local,
+            ollama_config("http://localhost:11434").request_signature()
+        );
+        assert_ne!(
+            local,
+            ollama_config("http://gpu-box:11434").request_signature()
+        );
+        assert!(local.contains("\"num_ctx\":4096"));
+        assert!(local.contains("\"temperature\":0.2"));
+    }
+
+    #[test]
+    fn gemini_schema_drops_additional_properties() {
+        let schema = json!({
+            "type": "object",
+            "additionalProperties": false,
+            "properties": {
+                "items": {
+                    "type": "array",
+                    "items": { "type": "object", "additionalProperties": false }
+                }
+            }
+        });
+        assert_eq!(
+            gemini_schema(&schema),
+            json!({
+                "type": "object",
+                "properties": {
+                    "items": { "type": "array", "items": { "type": "object" } }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn response_format_description_includes_schema() {
+        let format = ResponseFormat::JsonSchema {
+            name: "analysis",
+            schema: json!({ "type": "object" }),
+        };
+        assert_eq!(
+            format.describe(),
+            "json_schema:analysis:{\"type\":\"object\"}"
+        );
+        assert_eq!(ResponseFormat::Text.describe(), "text");
+    }
+}
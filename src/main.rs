@@ -1,14 +1,60 @@
-use anyhow::{Context, Result};
-use minijinja::{Environment, context};
+use anyhow::{Context, Result, bail};
+use minijinja::{Environment, Value, context};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
+mod audit;
+mod bilingual;
+mod budget;
+mod cache;
+mod chapters;
+mod characters;
+mod cli;
+mod document;
+mod docx;
+mod encoding;
+mod estimate;
+mod import;
+mod json_repair;
+mod language;
 mod llm;
-
-use crate::llm::{LlmClient, LlmConfig, create_llm_client};
+mod memory;
+mod notes;
+mod output;
+mod postprocess;
+mod prompts;
+mod replay;
+mod ruby;
+mod session;
+mod text;
+mod toc;
+mod usage;
+
+use crate::audit::AuditLog;
+use crate::bilingual::{BilingualFormat, align};
+use crate::budget::{BudgetConfig, DailyLedger};
+use crate::cache::{CacheConfig, CachedClient};
+use crate::characters::{Character, CharacterRegistry, character_schema};
+use crate::cli::{USAGE, parse_args};
+use crate::document::{Document, InputFormat};
+use crate::encoding::EncodingConfig;
+use crate::estimate::ChapterEstimate;
+use crate::import::ImportConfig;
+use crate::json_repair::repair_json_object;
+use crate::language::resolve_source_language;
+use crate::llm::{LlmConfig, ResponseFormat, create_llm_client};
+use crate::memory::StoryMemory;
+use crate::notes::{Note, NotesOutput};
+use crate::output::OutputNames;
+use crate::prompts::{PromptsConfig, build_environment};
+use crate::replay::ReplayClient;
+use crate::ruby::Reading;
+use crate::session::LlmSession;
+use crate::toc::{ChapterStatus, TocEntry, TocFormat, TocTranslation};
+use crate::usage::{UsageTracker, format_usage};
 
 // --- 結構定義 ---
 
@@ -19,31 +65,170 @@ struct Config {
     constraints: ConstraintsConfig,
     runtime: RuntimeConfig,
     prompts: PromptsConfig,
+    #[serde(default)]
+    cache: CacheConfig,
+    #[serde(default)]
+    novel: NovelConfig,
+    #[serde(default)]
+    import: ImportConfig,
+    #[serde(default)]
+    encoding: EncodingConfig,
+    #[serde(default)]
+    postprocess: Vec<postprocess::Rule>, // 依序套用在 Pass 2 譯文上的後處理
+}
+
+// 作品資訊，提供給模板 (novel_title / genre / style_notes)
+#[derive(Debug, Deserialize, Default)]
+struct NovelConfig {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    genre: String,
+    #[serde(default)]
+    style_notes: String, // 使用者自訂的翻譯風格說明
 }
 
 #[derive(Debug, Deserialize)]
 struct TranslationConfig {
+    #[serde(default = "default_source_language")]
+    source_language: String, // "auto" 表示逐章偵測
+    #[serde(default)]
     target_language: String,
+    #[serde(default)]
+    target_languages: Vec<String>, // 多個目標語言：Pass 1 只做一次，Pass 2 逐語言翻譯
     input_folder: PathBuf,
     output_folder: PathBuf,
     glossary_folder: PathBuf,
+    #[serde(default)]
+    style_context_paragraphs: usize, // 傳給 Pass 2 的上一章結尾段落數，0 表示停用
+    #[serde(default)]
+    bilingual_formats: Vec<BilingualFormat>, // 額外輸出原文/譯文對照版
+    #[serde(default)]
+    chapter_order: Option<PathBuf>, // 章節清單檔，未設定時依檔名自然排序
+    #[serde(default)]
+    ruby_notes: bool, // 在譯文最後附上本章讀音標註 (ruby) 的譯註
+    #[serde(default)]
+    translator_notes: bool, // 要求 Pass 2 回傳譯註 (模板變數 translator_notes)
+    #[serde(default)]
+    notes_output: NotesOutput, // 譯註加在譯文中或另存檔案
+    #[serde(default)]
+    notes_headings: BTreeMap<String, String>, // 各目標語言的譯註標題，未設定時依語言使用 譯註、Translator's Notes 等
+    #[serde(default)]
+    output_filename: Option<String>, // 譯文檔名模板 (minijinja)，未設定時沿用原檔名
+}
+
+fn default_source_language() -> String {
+    language::AUTO.to_string()
+}
+
+impl TranslationConfig {
+    /// 所有目標語言；第一個為主要語言 (字典 terms 與角色譯名使用)
+    fn targets(&self) -> Vec<&str> {
+        if self.target_languages.is_empty() {
+            vec![self.target_language.as_str()]
+        } else {
+            self.target_languages.iter().map(String::as_str).collect()
+        }
+    }
+
+    fn primary_target(&self) -> &str {
+        self.targets()[0]
+    }
+
+    fn notes_heading<'a>(&'a self, language: &str) -> &'a str {
+        match self.notes_headings.get(language) {
+            Some(heading) => heading,
+            None => notes::default_heading(language),
+        }
+    }
+
+    /// 譯文輸出資料夾；設定 target_languages 時每個語言使用 output_folder/<語言> 子資料夾
+    fn output_folder_for(&self, language: &str) -> PathBuf {
+        if self.target_languages.is_empty() {
+            self.output_folder.clone()
+        } else {
+            self.output_folder.join(language_folder_name(language))
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        if !self.target_language.is_empty() && !self.target_languages.is_empty() {
+            bail!("translation.target_language 與 target_languages 只能擇一設定");
+        }
+        let targets = self.targets();
+        if targets.iter().any(|lang| lang.trim().is_empty()) {
+            bail!("請設定 translation.target_language 或 target_languages");
+        }
+        let mut folders: Vec<String> = targets
+            .iter()
+            .map(|lang| language_folder_name(lang))
+            .collect();
+        folders.sort();
+        folders.dedup();
+        if folders.len() != targets.len() {
+            bail!("target_languages 中有重複的語言: {}", targets.join(", "));
+        }
+        if let Some(language) = self
+            .notes_headings
+            .keys()
+            .find(|language| !targets.contains(&language.as_str()))
+        {
+            bail!(
+                "translation.notes_headings 中有不在翻譯目標內的語言: {} (目標: {})",
+                language,
+                targets.join(", ")
+            );
+        }
+        Ok(())
+    }
+}
+
+// 語言名稱轉為資料夾名稱，例如 "Traditional Chinese (Taiwan)" -> "traditional-chinese-taiwan"
+fn language_folder_name(language: &str) -> String {
+    language
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
 }
 
 #[derive(Debug, Deserialize)]
 struct ConstraintsConfig {
     max_summary_length: usize,
     max_dictionary_size: usize,
+    #[serde(default)]
+    context_window: Option<usize>, // 模型 context 上限，dry-run 用來警告過長章節
+    #[serde(default = "default_story_arc_interval")]
+    story_arc_interval: usize, // 每累積 N 章摘要就壓縮成一段劇情，0 表示不壓縮
+    #[serde(default = "default_max_story_arcs")]
+    max_story_arcs: usize, // 劇情段落超過 N 段就合併成一段，0 表示不限制
+}
+
+fn default_story_arc_interval() -> usize {
+    10
+}
+
+fn default_max_story_arcs() -> usize {
+    5
 }
 
+// 不壓縮時保留的逐章摘要數
+const RECENT_SUMMARIES_WITHOUT_ARCS: usize = 10;
+
 #[derive(Debug, Deserialize)]
 struct RuntimeConfig {
     unattended_mode: bool,
+    #[serde(default)]
+    budget: BudgetConfig,
+    #[serde(default)]
+    audit_log: Option<PathBuf>, // 每次 LLM 呼叫的 JSONL 稽核紀錄
+    #[serde(default = "default_analysis_retries")]
+    analysis_retries: usize, // Pass 1 JSON 解析失敗時重新要求的次數
 }
 
-#[derive(Debug, Deserialize)]
-struct PromptsConfig {
-    analysis_prompt: String,
-    translation_prompt: String,
+fn default_analysis_retries() -> usize {
+    2
 }
 
 // 字典檔案格式
@@ -52,13 +237,135 @@ struct ChapterGlossary {
     chapter_name: String,
     summary: String,                // 本章結束後的劇情摘要
     terms: HashMap<String, String>, // 累積到本章為止的所有名詞
+    #[serde(default)]
+    memory: StoryMemory, // 累積到本章為止的多章劇情記憶
+    #[serde(default)]
+    characters: CharacterRegistry, // 累積到本章為止的角色表
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    term_columns: BTreeMap<String, HashMap<String, String>>, // 其他目標語言的名詞譯名 (語言 -> 名詞 -> 譯名)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    readings: BTreeMap<String, String>, // 累積的讀音標註 (本文 -> 讀音)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    outputs: BTreeMap<String, PathBuf>, // 本章各語言譯文的路徑 (相對於該語言的輸出資料夾)
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    source_title: String, // 原文的章節名稱 (第一個非空白行)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    titles: BTreeMap<String, String>, // 本章各語言譯文的章節名稱
+}
+
+impl ChapterGlossary {
+    // 指定目標語言使用的名詞對照；主要語言使用 terms
+    fn terms_for(&self, primary: &str, language: &str) -> HashMap<String, String> {
+        if language == primary {
+            self.terms.clone()
+        } else {
+            self.term_columns.get(language).cloned().unwrap_or_default()
+        }
+    }
 }
 
 // Pass 1 AI 回應格式
 #[derive(Debug, Deserialize)]
 struct AnalysisResponse {
     summary: String,
+    #[serde(deserialize_with = "deserialize_glossary")]
     new_glossary: HashMap<String, String>,
+    #[serde(default)]
+    characters: Vec<Character>, // 本章登場或資料有變化的角色
+    #[serde(default)]
+    translated_glossaries: Vec<LanguageGlossary>, // 其他目標語言的新名詞譯名
+}
+
+#[derive(Debug, Deserialize)]
+struct LanguageGlossary {
+    language: String,
+    #[serde(deserialize_with = "deserialize_glossary")]
+    new_glossary: HashMap<String, String>,
+}
+
+// structured output 無法表達任意 key 的物件，因此 schema 以 [{term, translation}] 表示字典
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum GlossaryEntries {
+    Map(HashMap<String, String>),
+    List(Vec<GlossaryEntry>),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GlossaryEntry {
+    term: String,
+    translation: String,
+}
+
+fn deserialize_glossary<'de, D>(deserializer: D) -> Result<HashMap<String, String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(match GlossaryEntries::deserialize(deserializer)? {
+        GlossaryEntries::Map(map) => map,
+        GlossaryEntries::List(list) => list
+            .into_iter()
+            .map(|entry| (entry.term, entry.translation))
+            .collect(),
+    })
+}
+
+impl AnalysisResponse {
+    /// 傳給各 provider structured output 的 JSON Schema；extra_languages 為主要語言以外的目標語言
+    fn json_schema(extra_languages: &[&str]) -> serde_json::Value {
+        let glossary = serde_json::json!({
+            "type": "array",
+            "items": {
+                "type": "object",
+                "properties": {
+                    "term": { "type": "string" },
+                    "translation": { "type": "string" }
+                },
+                "required": ["term", "translation"],
+                "additionalProperties": false
+            }
+        });
+        let mut schema = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "summary": { "type": "string" },
+                "new_glossary": glossary,
+                "characters": { "type": "array", "items": character_schema() }
+            },
+            "required": ["summary", "new_glossary", "characters"],
+            "additionalProperties": false
+        });
+        if !extra_languages.is_empty() {
+            schema["properties"]["translated_glossaries"] = serde_json::json!({
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "language": { "type": "string", "enum": extra_languages },
+                        "new_glossary": glossary
+                    },
+                    "required": ["language", "new_glossary"],
+                    "additionalProperties": false
+                }
+            });
+            schema["required"]
+                .as_array_mut()
+                .unwrap()
+                .push("translated_glossaries".into());
+        }
+        schema
+    }
+
+    fn response_format(config: &Config) -> ResponseFormat {
+        if config.llm.structured_output {
+            ResponseFormat::JsonSchema {
+                name: "chapter_analysis",
+                schema: Self::json_schema(&config.translation.targets()[1..]),
+            }
+        } else {
+            ResponseFormat::Json
+        }
+    }
 }
 
 // --- 輔助函式 ---
@@ -99,10 +406,56 @@ fn sanitize_json_response(raw_resp: &str) -> String {
     without_closing.trim().to_string()
 }
 
-fn is_txt_file(path: &Path) -> bool {
-    path.extension()
-        .and_then(|ext| ext.to_str())
-        .is_some_and(|ext| ext.eq_ignore_ascii_case("txt"))
+// 解析 Pass 1 回應：先嚴格解析，失敗時再嘗試修復 JSON
+fn parse_analysis_response(raw_resp: &str) -> Result<AnalysisResponse> {
+    let clean_json = sanitize_json_response(raw_resp);
+    if let Ok(analysis) = serde_json::from_str(&clean_json) {
+        return Ok(analysis);
+    }
+    let repaired = repair_json_object(&clean_json).context("回應中找不到 JSON 物件")?;
+    Ok(serde_json::from_str(&repaired)?)
+}
+
+// 附加在 analysis prompt 後，要求模型修正格式；必要欄位取自 JSON Schema
+fn analysis_retry_prompt(
+    analysis_prompt: &str,
+    schema: &serde_json::Value,
+    error: &anyhow::Error,
+) -> String {
+    let fields: Vec<String> = schema["required"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|field| field.as_str())
+        .map(|field| format!("\"{}\"", field))
+        .collect();
+    format!(
+        "{}\n\n注意：前一次的回應無法解析 ({:#})。\n\
+         請只回傳一個與上面範例格式相同的 JSON 物件，必須包含 {} 欄位；\
+         new_glossary 為 [{{\"term\": \"原文\", \"translation\": \"譯名\"}}] 形式的陣列，不要加入任何其他文字。",
+        analysis_prompt,
+        error,
+        fields.join("、")
+    )
+}
+
+// 支援的章節檔：.txt / .md / .html / .docx
+fn is_input_file(path: &Path) -> bool {
+    InputFormat::from_path(path).is_some()
+}
+
+// 讀取章節並依副檔名解析結構
+fn read_document(config: &Config, path: &Path, output: bool) -> Result<Document> {
+    let format = InputFormat::from_path(path).unwrap_or(InputFormat::Text);
+    if format == InputFormat::Docx {
+        return Document::from_docx(fs_err::read(path)?);
+    }
+    let text = if output {
+        config.encoding.read_output(path)?
+    } else {
+        config.encoding.read_input(path)?
+    };
+    Document::parse(format, &text)
 }
 
 fn resolve_start_index(input: &str, suggested_index: usize, files_len: usize) -> (Option<usize>, bool) {
@@ -119,93 +472,785 @@ fn resolve_start_index(input: &str, suggested_index: usize, files_len: usize) ->
     }
 }
 
-// --- 核心處理 ---
+// 目前章節在整部作品中的位置
+#[derive(Debug, Clone)]
+struct ChapterInfo {
+    index: usize, // 從 1 開始
+    count: usize,
+    name: String,
+    source_lang: String,
+    target_lang: String, // Pass 1 為主要語言，Pass 2 為目前翻譯的語言
+    input_format: &'static str,
+    readings: Vec<Reading>, // 本章的讀音標註與先前累積、本章也出現的讀音
+}
 
-async fn process_chapter(
-    llm: &dyn LlmClient,
-    config: &Config,
-    prompt_env: &Environment<'_>,
-    file_path: &Path,
-    previous_glossary: &ChapterGlossary,
-) -> Result<ChapterGlossary> {
-    let file_stem = file_path.file_stem().unwrap().to_string_lossy().to_string();
-    let file_name = file_path.file_name().unwrap().to_string_lossy().to_string();
+impl ChapterInfo {
+    fn new(config: &TranslationConfig, files: &[PathBuf], index: usize) -> Self {
+        Self {
+            index: index + 1,
+            count: files.len(),
+            name: chapters::chapter_id(&config.input_folder, &files[index]),
+            source_lang: String::new(),
+            target_lang: config.primary_target().to_string(),
+            input_format: InputFormat::from_path(&files[index])
+                .unwrap_or(InputFormat::Text)
+                .name(),
+            readings: Vec::new(),
+        }
+    }
 
-    println!("正在處理: {}", file_name);
-    let content = fs_err::read_to_string(file_path)?;
+    // 依本章內文決定 source_lang，語言與設定不符時印出警告
+    fn detect_source_language(&mut self, config: &TranslationConfig, content: &str) {
+        let result = resolve_source_language(&config.source_language, &config.targets(), content);
+        for warning in result.warnings {
+            println!("    [警告] {}: {}", self.name, warning);
+        }
+        self.source_lang = result.name;
+    }
+}
 
-    // === Pass 1: 分析 (基於上一章的字典與摘要) ===
-    println!("  > Pass 1: 分析文本與提取新詞...");
+// 依名詞排序的字典清單，方便模板以 for 迴圈輸出
+fn glossary_entries(terms: &HashMap<String, String>) -> Vec<GlossaryEntry> {
+    let mut entries: Vec<GlossaryEntry> = terms
+        .iter()
+        .map(|(term, translation)| GlossaryEntry {
+            term: term.clone(),
+            translation: translation.clone(),
+        })
+        .collect();
+    entries.sort_by(|a, b| a.term.cmp(&b.term));
+    entries
+}
 
+// 所有模板共用的變數；config 中的自訂變數會被同名的內建變數覆蓋
+fn base_context(config: &Config, chapter: &ChapterInfo) -> Value {
+    context! {
+        target_lang => chapter.target_lang,
+        target_langs => config.translation.targets(),
+        source_lang => chapter.source_lang,
+        novel_title => config.novel.title,
+        genre => config.novel.genre,
+        style_notes => config.novel.style_notes,
+        chapter_index => chapter.index,
+        chapter_count => chapter.count,
+        chapter_name => chapter.name,
+        input_format => chapter.input_format,
+        readings => chapter.readings,
+        translator_notes => config.translation.translator_notes,
+        ..Value::from_serialize(&config.prompts.variables)
+    }
+}
+
+fn render_analysis_prompt(
+    config: &Config,
+    prompt_env: &Environment<'_>,
+    chapter: &ChapterInfo,
+    previous_glossary: &ChapterGlossary,
+) -> Result<String> {
     let base_terms_json = serde_json::to_string(&previous_glossary.terms)?;
 
     // 使用 minijinja 渲染 prompt
     let tmpl = prompt_env.get_template("analysis")?;
-    let analysis_prompt = tmpl.render(context! {
-        target_lang => config.translation.target_language,
+    let prompt = tmpl.render(context! {
         summary_len => config.constraints.max_summary_length,
         glossary_limit => config.constraints.max_dictionary_size,
         prev_summary => previous_glossary.summary,
-        existing_glossary => base_terms_json
+        existing_glossary => base_terms_json,
+        existing_glossary_terms => glossary_entries(&previous_glossary.terms),
+        extra_target_langs => config.translation.targets()[1..],
+        story_so_far => previous_glossary.memory.story_so_far(),
+        recent_summaries => previous_glossary.memory.recent,
+        existing_characters => previous_glossary.characters.list(),
+        ..base_context(config, chapter)
+    })?;
+    Ok(prompt)
+}
+
+// 上一章原文與譯文的結尾，讓 Pass 2 延續語氣與用詞
+#[derive(Debug, Default)]
+struct PreviousTail {
+    source: String,
+    translation: String,
+}
+
+// 章節譯文的路徑：優先使用字典中記錄的路徑，否則依檔名模板 (不含譯名) 推算
+fn chapter_output_path(
+    config: &Config,
+    output_names: &OutputNames,
+    file: &Path,
+    index: usize,
+    count: usize,
+    language: &str,
+    glossary: &ChapterGlossary,
+) -> Result<PathBuf> {
+    let folder = config.translation.output_folder_for(language);
+    if let Some(path) = glossary.outputs.get(language) {
+        return Ok(folder.join(path));
+    }
+    let relative = output_names.relative_path(
+        &config.translation.input_folder,
+        file,
+        index,
+        count,
+        language,
+        "",
+    )?;
+    Ok(folder.join(relative))
+}
+
+// 章節進度：沒有字典為 pending，有字典但有語言的譯文不存在為 analyzed
+fn chapter_status(
+    config: &Config,
+    output_names: &OutputNames,
+    files: &[PathBuf],
+    index: usize,
+    glossary: Option<&ChapterGlossary>,
+) -> Result<ChapterStatus> {
+    let Some(glossary) = glossary else {
+        return Ok(ChapterStatus::Pending);
+    };
+    for language in config.translation.targets() {
+        let path = chapter_output_path(
+            config,
+            output_names,
+            &files[index],
+            index + 1,
+            files.len(),
+            language,
+            glossary,
+        )?;
+        if !path.exists() {
+            return Ok(ChapterStatus::Analyzed);
+        }
+    }
+    Ok(ChapterStatus::Translated)
+}
+
+// 依字典與輸出檔整理目錄 (--toc)，寫入 output_folder/toc.<副檔名>
+fn write_toc(
+    config: &Config,
+    output_names: &OutputNames,
+    files: &[PathBuf],
+    format: TocFormat,
+) -> Result<PathBuf> {
+    let languages = config.translation.targets();
+    let mut entries = Vec::new();
+    for (index, file_path) in files.iter().enumerate() {
+        let id = chapters::chapter_id(&config.translation.input_folder, file_path);
+        let glossary = load_glossary(&config.translation.glossary_folder, &id);
+        let status = chapter_status(config, output_names, files, index, glossary.as_ref())?;
+        let glossary = glossary.unwrap_or_default();
+
+        let document = read_document(config, file_path, false)?;
+        let (source, _) = strip_ruby(config, &document.source_text());
+        let source_title = match glossary.source_title.as_str() {
+            "" => output::first_line_title(&source),
+            title => title.to_string(),
+        };
+        let mut translations = Vec::new();
+        for language in &languages {
+            let path = chapter_output_path(
+                config,
+                output_names,
+                file_path,
+                index + 1,
+                files.len(),
+                language,
+                &glossary,
+            )?;
+            let translation = if path.exists() {
+                let text = read_document(config, &path, true)?.source_text();
+                notes::strip_endnotes(&text, config.translation.notes_heading(language))
+            } else {
+                String::new()
+            };
+            // 舊版字典沒有記錄譯名時，從譯文第一行取得
+            let title = match glossary.titles.get(*language) {
+                Some(title) => title.clone(),
+                None => output::first_line_title(&translation),
+            };
+            translations.push(TocTranslation {
+                language: language.to_string(),
+                title,
+                words: toc::word_count(&output::strip_tags(&translation)),
+            });
+        }
+
+        let file = file_path
+            .strip_prefix(&config.translation.input_folder)
+            .unwrap_or(file_path);
+        entries.push(TocEntry {
+            index: index + 1,
+            file: file.display().to_string(),
+            source_title,
+            source_words: toc::word_count(&output::strip_tags(&source)),
+            translations,
+            status,
+        });
+    }
+
+    fs_err::create_dir_all(&config.translation.output_folder)?;
+    let path = config
+        .translation
+        .output_folder
+        .join(format!("toc.{}", format.extension()));
+    fs_err::write(&path, toc::render(format, &entries, &languages)?)?;
+    Ok(path)
+}
+
+// 半形 | 與 漢字《》、漢字（かな） 等 ruby 寫法只在日文文本中處理，避免刪掉一般文章的文字
+fn strip_ruby(config: &Config, text: &str) -> (String, Vec<Reading>) {
+    let japanese = language::is_japanese(&config.translation.source_language, text);
+    ruby::strip(text, japanese)
+}
+
+// previous 為上一章的 (原文, 譯文) 路徑
+fn load_previous_tail(
+    config: &Config,
+    language: &str,
+    previous: Option<(&Path, &Path)>,
+) -> PreviousTail {
+    let count = config.translation.style_context_paragraphs;
+    let Some((previous_file, output_path)) = previous.filter(|_| count > 0) else {
+        return PreviousTail::default();
+    };
+
+    let tail = |text: Result<String>| {
+        text.map(|text| text::tail_paragraphs(&text, count))
+            .unwrap_or_default()
+    };
+    let read = |path: &Path, output: bool| {
+        tail(read_document(config, path, output).map(|doc| {
+            let text = doc.source_text();
+            let heading = config.translation.notes_heading(language);
+            strip_ruby(config, &notes::strip_endnotes(&text, heading)).0
+        }))
+    };
+    PreviousTail {
+        source: read(previous_file, false),
+        translation: read(output_path, true),
+    }
+}
+
+fn render_translation_prompt(
+    config: &Config,
+    prompt_env: &Environment<'_>,
+    chapter: &ChapterInfo,
+    chapter_data: &ChapterGlossary,
+    content: &str,
+    previous_tail: &PreviousTail,
+) -> Result<String> {
+    let terms = chapter_data.terms_for(config.translation.primary_target(), &chapter.target_lang);
+    let final_terms_json = serde_json::to_string(&terms)?;
+    // 只提供本章出現的角色；角色譯名只有主要語言，其他語言不提供以免混用
+    let mut characters: Vec<Character> = chapter_data
+        .characters
+        .mentioned_in(content)
+        .into_iter()
+        .cloned()
+        .collect();
+    if chapter.target_lang != config.translation.primary_target() {
+        for character in &mut characters {
+            character.translated_name.clear();
+        }
+    }
+
+    let tmpl = prompt_env.get_template("translation")?;
+    let prompt = tmpl.render(context! {
+        summary => chapter_data.summary,
+        story_so_far => chapter_data.memory.story_so_far(),
+        recent_summaries => chapter_data.memory.recent,
+        characters => characters,
+        glossary => final_terms_json,
+        glossary_terms => glossary_entries(&terms),
+        prev_source_tail => previous_tail.source,
+        prev_translation_tail => previous_tail.translation,
+        ..base_context(config, chapter)
     })?;
+    Ok(prompt)
+}
 
-    let raw_resp = llm.generate(&analysis_prompt, &content, true).await?;
+fn render_story_arc_prompt(
+    config: &Config,
+    prompt_env: &Environment<'_>,
+    chapter: &ChapterInfo,
+    memory: &StoryMemory,
+) -> Result<String> {
+    let tmpl = prompt_env.get_template("story_arc")?;
+    let prompt = tmpl.render(context! {
+        summary_len => config.constraints.max_summary_length,
+        story_so_far => memory.story_so_far(),
+        ..base_context(config, chapter)
+    })?;
+    Ok(prompt)
+}
+
+// 啟動時以空白資料渲染一次所有模板，提早發現未定義的變數
+fn validate_prompts(config: &Config, prompt_env: &Environment<'_>) -> Result<()> {
+    let chapter = ChapterInfo {
+        index: 1,
+        count: 1,
+        name: String::new(),
+        source_lang: config.translation.source_language.clone(),
+        target_lang: config.translation.primary_target().to_string(),
+        input_format: InputFormat::Text.name(),
+        readings: Vec::new(),
+    };
+    let glossary = ChapterGlossary::default();
+    render_analysis_prompt(config, prompt_env, &chapter, &glossary).context("analysis 模板錯誤")?;
+    render_translation_prompt(
+        config,
+        prompt_env,
+        &chapter,
+        &glossary,
+        "",
+        &PreviousTail::default(),
+    )
+    .context("translation 模板錯誤")?;
+    render_story_arc_prompt(config, prompt_env, &chapter, &glossary.memory)
+        .context("story_arc 模板錯誤")?;
+    Ok(())
+}
+
+// --- 核心處理 ---
+
+async fn process_chapter(
+    session: &mut LlmSession,
+    config: &Config,
+    prompt_env: &Environment<'_>,
+    output_names: &OutputNames,
+    files: &[PathBuf],
+    index: usize,
+    previous_glossary: &ChapterGlossary,
+) -> Result<ChapterGlossary> {
+    let mut chapter = ChapterInfo::new(&config.translation, files, index);
+    let file_path = files[index].as_path();
+    let previous_file = index.checked_sub(1).map(|i| files[i].as_path());
+    let chapter_id = chapter.name.clone();
+    let file_name = file_path.file_name().unwrap().to_string_lossy().to_string();
+
+    println!("正在處理: {}", file_name);
+    let document = read_document(config, file_path, false)?;
+    // ruby 標記不交給模型，讀音改為 prompt 中的提示
+    let (content, readings) = strip_ruby(config, &document.source_text());
+    chapter.detect_source_language(&config.translation, &content);
+    chapter.readings = ruby::relevant(&previous_glossary.readings, &readings, &content);
 
-    let clean_json = sanitize_json_response(&raw_resp);
+    // === Pass 1: 分析 (基於上一章的字典與摘要) ===
+    println!("  > Pass 1: 分析文本與提取新詞...");
 
-    let analysis: AnalysisResponse = serde_json::from_str(&clean_json)
-        .context(format!("Pass 1 JSON 解析失敗，原始回應: {}", raw_resp))?;
+    let analysis_prompt = render_analysis_prompt(config, prompt_env, &chapter, previous_glossary)?;
+
+    let analysis_format = AnalysisResponse::response_format(config);
+    let mut prompt = analysis_prompt.clone();
+    let mut attempt = 0;
+    let analysis = loop {
+        let resp = session
+            .generate(&chapter_id, "analysis", &prompt, &content, &analysis_format)
+            .await?;
+        match parse_analysis_response(&resp.text) {
+            Ok(analysis) => break analysis,
+            Err(e) if attempt < config.runtime.analysis_retries => {
+                attempt += 1;
+                println!(
+                    "    - JSON 解析失敗 ({:#})，要求模型重新輸出 (第 {} 次)...",
+                    e, attempt
+                );
+                let schema = AnalysisResponse::json_schema(&config.translation.targets()[1..]);
+                prompt = analysis_retry_prompt(&analysis_prompt, &schema, &e);
+            }
+            Err(e) => {
+                return Err(e.context(format!("Pass 1 JSON 解析失敗，原始回應: {}", resp.text)));
+            }
+        }
+    };
 
     // 合併字典：舊字典 + 新字典
     let mut current_terms = previous_glossary.terms.clone();
     current_terms.extend(analysis.new_glossary);
 
-    let current_chapter_data = ChapterGlossary {
-        chapter_name: file_stem.clone(),
+    let extra_languages = &config.translation.targets()[1..];
+    let mut term_columns = previous_glossary.term_columns.clone();
+    for glossary in analysis.translated_glossaries {
+        if !extra_languages.contains(&glossary.language.as_str()) {
+            println!("    - 略過未設定的語言字典: {}", glossary.language);
+            continue;
+        }
+        term_columns
+            .entry(glossary.language)
+            .or_default()
+            .extend(glossary.new_glossary);
+    }
+
+    let mut characters = previous_glossary.characters.clone();
+    characters.merge(analysis.characters);
+
+    let mut known_readings = previous_glossary.readings.clone();
+    for reading in &readings {
+        known_readings
+            .entry(reading.base.clone())
+            .or_insert_with(|| reading.reading.clone());
+    }
+
+    let mut memory = previous_glossary.memory.clone();
+    memory.record(&chapter_id, &analysis.summary);
+    let interval = config.constraints.story_arc_interval;
+    if memory.needs_compression(interval) {
+        println!("  > 壓縮最近 {} 章的劇情摘要...", memory.recent.len());
+        let arc_prompt = render_story_arc_prompt(config, prompt_env, &chapter, &memory)?;
+        let resp = session
+            .generate(
+                &chapter_id,
+                "story_arc",
+                &arc_prompt,
+                &memory.compression_input(),
+                &ResponseFormat::Text,
+            )
+            .await?;
+        memory.compress(resp.text.trim().to_string());
+
+        if memory.too_many_arcs(config.constraints.max_story_arcs) {
+            println!("  > 合併 {} 段劇情段落...", memory.arcs.len());
+            // 要合併的段落作為內文，prompt 中不再重複
+            let merge_prompt =
+                render_story_arc_prompt(config, prompt_env, &chapter, &StoryMemory::default())?;
+            let resp = session
+                .generate(
+                    &chapter_id,
+                    "story_arc",
+                    &merge_prompt,
+                    &memory.story_so_far(),
+                    &ResponseFormat::Text,
+                )
+                .await?;
+            memory.merge_arcs(resp.text.trim().to_string());
+        }
+    } else if interval == 0 {
+        memory.trim_recent(RECENT_SUMMARIES_WITHOUT_ARCS);
+    }
+
+    let mut current_chapter_data = ChapterGlossary {
+        chapter_name: chapter_id.clone(),
         summary: analysis.summary,
         terms: current_terms,
+        memory,
+        characters,
+        term_columns,
+        readings: known_readings,
+        outputs: BTreeMap::new(),
+        source_title: output::first_line_title(&content),
+        titles: BTreeMap::new(),
     };
-    
+
     save_glossary(
         &config.translation.glossary_folder,
-        &file_stem,
+        &chapter_id,
         &current_chapter_data,
-    ).await?;
+    )
+    .await?;
     println!(
-        "    - 字典已存檔至 glossaries/{}.json (目前詞條數: {})",
-        file_stem,
-        current_chapter_data.terms.len()
+        "    - 字典已存檔至 glossaries/{}.json (目前詞條數: {}，角色數: {})",
+        chapter_id,
+        current_chapter_data.terms.len(),
+        current_chapter_data.characters.len()
     );
 
-    // === Pass 2: 翻譯 ===
-    println!("  > Pass 2: 翻譯中...");
+    // === Pass 2: 翻譯 (每個目標語言各一次) ===
+    let multilingual = !config.translation.target_languages.is_empty();
+    for language in config.translation.targets() {
+        chapter.target_lang = language.to_string();
+        let pass = if multilingual {
+            println!("  > Pass 2: 翻譯中 ({})...", language);
+            format!("translation:{}", language)
+        } else {
+            println!("  > Pass 2: 翻譯中...");
+            "translation".to_string()
+        };
+
+        let previous_output = previous_file
+            .map(|file| {
+                chapter_output_path(
+                    config,
+                    output_names,
+                    file,
+                    chapter.index - 1,
+                    chapter.count,
+                    language,
+                    previous_glossary,
+                )
+            })
+            .transpose()?;
+        let previous_tail = load_previous_tail(
+            config,
+            language,
+            previous_file.zip(previous_output.as_deref()),
+        );
+        let trans_prompt = render_translation_prompt(
+            config,
+            prompt_env,
+            &chapter,
+            &current_chapter_data,
+            &content,
+            &previous_tail,
+        )?;
+
+        let resp = session
+            .generate(
+                &chapter_id,
+                &pass,
+                &trans_prompt,
+                &content,
+                &ResponseFormat::Text,
+            )
+            .await?;
+
+        let (translated_text, mut notes) = notes::split_response(&resp.text.replace("\\n", "\n"));
+        let translated_text = postprocess::apply(&config.postprocess, language, &translated_text)?;
+        // 譯註的 anchor 也做相同處理，才能在譯文中找到
+        for note in &mut notes {
+            note.anchor = postprocess::apply(&config.postprocess, language, &note.anchor)?
+                .trim()
+                .to_string();
+        }
+
+        // 寫入翻譯結果 (檔名模板可使用譯文第一行的章節名稱)
+        let translated_title = output::first_line_title(&translated_text);
+        let relative_path = output_names.relative_path(
+            &config.translation.input_folder,
+            file_path,
+            chapter.index,
+            chapter.count,
+            language,
+            &translated_title,
+        )?;
+        let output_path = config
+            .translation
+            .output_folder_for(language)
+            .join(&relative_path);
+        if let Some(folder) = output_path.parent() {
+            fs_err::create_dir_all(folder)?;
+        }
 
-    let final_terms_json = serde_json::to_string(&current_chapter_data.terms)?;
+        let primary = config.translation.primary_target();
+        let terms = current_chapter_data.terms_for(primary, language);
+        if config.translation.ruby_notes {
+            notes.extend(
+                ruby::notes(&readings, &terms)
+                    .into_iter()
+                    .map(Note::endnote),
+            );
+        }
+        // HTML 原文的讀音標註加回譯文 (以譯名標註)
+        let ruby = ruby::translated(&readings, &terms);
+        match config.translation.notes_output {
+            NotesOutput::Inline => document.write(
+                &output_path,
+                &translated_text,
+                &notes,
+                config.translation.notes_heading(language),
+                &ruby,
+                &config.encoding,
+            )?,
+            NotesOutput::File => {
+                document.write(
+                    &output_path,
+                    &translated_text,
+                    &[],
+                    "",
+                    &ruby,
+                    &config.encoding,
+                )?;
+                if !notes.is_empty() {
+                    let notes_path = sibling_path(&output_path, "notes.json");
+                    notes::write_file(&notes_path, &chapter.name, &notes, &config.encoding)?;
+                }
+            }
+        }
+        if !notes.is_empty() {
+            println!("    - 譯註 {} 則", notes.len());
+        }
+        write_bilingual(config, &chapter, &output_path, &content, &translated_text)?;
+        println!("    - 譯文已寫入 {}", output_path.display());
+        current_chapter_data
+            .outputs
+            .insert(language.to_string(), relative_path);
+        current_chapter_data
+            .titles
+            .insert(language.to_string(), translated_title);
+    }
 
-    let tmpl = prompt_env.get_template("translation")?;
-    let trans_prompt = tmpl.render(context! {
-        target_lang => config.translation.target_language,
-        summary => current_chapter_data.summary,
-        glossary => final_terms_json
-    })?;
+    // 記錄譯文路徑與章節名稱，供下一章、續跑與目錄使用
+    save_glossary(
+        &config.translation.glossary_folder,
+        &chapter_id,
+        &current_chapter_data,
+    )
+    .await?;
+
+    if let Some(chapter_usage) = session.usage.chapter(&chapter_id) {
+        println!(
+            "    - 本章用量: {}",
+            format_usage(&chapter_usage.usage, chapter_usage.estimated_cost)
+        );
+    }
 
-    let mut translated_text = llm.generate(&trans_prompt, &content, false).await?;
+    Ok(current_chapter_data)
+}
 
-    translated_text = translated_text.replace("\\n", "\n");
+// 與譯文放在同一資料夾的附屬檔：<譯文檔名>.<suffix>
+fn sibling_path(output_path: &Path, suffix: &str) -> PathBuf {
+    let stem = output_path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy();
+    output_path.with_file_name(format!("{}.{}", stem, suffix))
+}
 
-    // 寫入翻譯結果
-    if !config.translation.output_folder.exists() {
-        fs_err::create_dir_all(&config.translation.output_folder)?;
+// 依設定輸出原文/譯文逐段對照版 (<譯文檔名>.bilingual.<副檔名>)
+fn write_bilingual(
+    config: &Config,
+    chapter: &ChapterInfo,
+    output_path: &Path,
+    source: &str,
+    translation: &str,
+) -> Result<()> {
+    if config.translation.bilingual_formats.is_empty() {
+        return Ok(());
+    }
+    let pairs = align(&text::paragraphs(source), &text::paragraphs(translation));
+    let source_label = if chapter.source_lang.is_empty() {
+        "原文"
+    } else {
+        &chapter.source_lang
+    };
+    for format in &config.translation.bilingual_formats {
+        let path = sibling_path(output_path, &format!("bilingual.{}", format.extension()));
+        let rendered = format.render(&chapter.name, source_label, &chapter.target_lang, &pairs);
+        let rendered = rendered.replace(
+            "<meta charset=\"utf-8\">",
+            &format!("<meta charset=\"{}\">", config.encoding.html_charset()),
+        );
+        config.encoding.write_output(&path, &rendered)?;
     }
+    println!("    - 已輸出對照版 ({} 組段落)", pairs.len());
+    Ok(())
+}
+
+// 不呼叫 LLM，只渲染 prompt 並估算 token
+fn dry_run(
+    config: &Config,
+    prompt_env: &Environment<'_>,
+    output_names: &OutputNames,
+    files: &[PathBuf],
+    start_index: usize,
+) -> Result<()> {
+    let mut glossary = start_index
+        .checked_sub(1)
+        .and_then(|i| {
+            let id = chapters::chapter_id(&config.translation.input_folder, &files[i]);
+            load_glossary(&config.translation.glossary_folder, &id)
+        })
+        .unwrap_or_default();
+    // 本章摘要尚未產生，以上限長度的佔位字串估算
+    let summary_placeholder = "字".repeat(config.constraints.max_summary_length);
+
+    let mut estimates = Vec::new();
+    for (index, file_path) in files.iter().enumerate().skip(start_index) {
+        let document = read_document(config, file_path, false)?;
+        let (content, readings) = strip_ruby(config, &document.source_text());
+        let mut chapter = ChapterInfo::new(&config.translation, files, index);
+        let chapter_id = chapter.name.clone();
+        chapter.detect_source_language(&config.translation, &content);
+        chapter.readings = ruby::relevant(&glossary.readings, &readings, &content);
+
+        let analysis_prompt = render_analysis_prompt(config, prompt_env, &chapter, &glossary)?;
+        // 此時 glossary 仍是上一章的資料，用來找上一章的譯文
+        let previous_file = index.checked_sub(1).map(|i| files[i].as_path());
+        let mut previous_outputs = Vec::new();
+        for language in config.translation.targets() {
+            let path = previous_file
+                .map(|file| {
+                    chapter_output_path(
+                        config,
+                        output_names,
+                        file,
+                        index,
+                        files.len(),
+                        language,
+                        &glossary,
+                    )
+                })
+                .transpose()?;
+            previous_outputs.push(path);
+        }
+        // 已有字典檔的章節用實際資料，否則沿用上一章的字典
+        if let Some(existing) = load_glossary(&config.translation.glossary_folder, &chapter_id) {
+            glossary = existing;
+        } else {
+            glossary.chapter_name = chapter_id.clone();
+            glossary.summary = summary_placeholder.clone();
+            glossary.outputs.clear();
+            glossary.titles.clear();
+            glossary.source_title = output::first_line_title(&content);
+            for reading in &readings {
+                glossary
+                    .readings
+                    .entry(reading.base.clone())
+                    .or_insert_with(|| reading.reading.clone());
+            }
+        }
+        let mut translation_prompts = Vec::new();
+        for (language, previous_output) in config
+            .translation
+            .targets()
+            .into_iter()
+            .zip(&previous_outputs)
+        {
+            chapter.target_lang = language.to_string();
+            // 上一章尚未翻譯時無法取得譯文結尾，以原文結尾長度估算
+            let mut previous_tail = load_previous_tail(
+                config,
+                language,
+                previous_file.zip(previous_output.as_deref()),
+            );
+            if previous_tail.translation.is_empty() {
+                previous_tail.translation = previous_tail.source.clone();
+            }
+            translation_prompts.push(render_translation_prompt(
+                config,
+                prompt_env,
+                &chapter,
+                &glossary,
+                &content,
+                &previous_tail,
+            )?);
+        }
 
-    let output_path = config.translation.output_folder.join(file_name);
-    fs_err::write(output_path, translated_text)?;
+        estimates.push(ChapterEstimate::new(
+            &chapter_id,
+            &analysis_prompt,
+            &translation_prompts,
+            &content,
+            config.constraints.max_summary_length,
+        ));
+    }
 
-    Ok(current_chapter_data)
+    estimate::print_report(
+        &estimates,
+        config.constraints.context_window,
+        config.llm.active_pricing(),
+    );
+    Ok(())
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let cli = parse_args(std::env::args().skip(1))?;
+    if cli.help {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+
     // 1. 設定讀取
     let config_path = if Path::new("config.yaml").exists() {
         "config.yaml"
@@ -215,12 +1260,32 @@ async fn main() -> Result<()> {
     let config_str =
         fs_err::read_to_string(config_path).context(format!("找不到 {}", config_path))?;
     let config: Config = serde_norway::from_str(&config_str)?;
-
-    let llm_client = create_llm_client(&config.llm)?;
-    let mut prompt_env = Environment::new();
-    prompt_env.add_template("analysis", &config.prompts.analysis_prompt)?;
-    prompt_env.add_template("translation", &config.prompts.translation_prompt)?;
-    println!("已初始化 LLM Provider: {}", config.llm.provider);
+    config.translation.validate()?;
+
+    let prompt_env = build_environment(&config.prompts)?;
+    validate_prompts(&config, &prompt_env)?;
+    let output_names = OutputNames::new(config.translation.output_filename.as_deref())?;
+    postprocess::validate(&config.postprocess, &config.translation.targets())?;
+    config.encoding.validate()?;
+
+    if let Some(book) = &cli.import {
+        let chapters = import::import_book(
+            book,
+            &config.translation.input_folder,
+            &config.import,
+            &config.encoding,
+        )?;
+        println!(
+            "已將 {} 切分為 {} 章，寫入 {:?}:",
+            book.display(),
+            chapters.len(),
+            config.translation.input_folder
+        );
+        for chapter in &chapters {
+            println!("  {}.txt  {}", chapter.id, chapter.title);
+        }
+        return Ok(());
+    }
 
     // 2. 獲取所有輸入檔案並排序
     if !config.translation.input_folder.exists() {
@@ -236,33 +1301,48 @@ async fn main() -> Result<()> {
     let mut files: Vec<PathBuf> = WalkDir::new(&config.translation.input_folder)
         .into_iter()
         .filter_map(|e| e.ok())
-        .filter(|e| e.path().is_file() && is_txt_file(e.path()))
+        .filter(|e| e.path().is_file() && is_input_file(e.path()))
         .map(|e| e.path().to_owned())
         .collect();
 
-    // 檔名自然排序
-    files.sort();
+    // 有章節清單時依清單順序，否則依路徑自然排序 (2.txt 在 10.txt 之前，vol2/ 在 vol10/ 之前)
+    if let Some(order_file) = &config.translation.chapter_order {
+        let (listed, unlisted) =
+            chapters::apply_order_file(&config.translation.input_folder, order_file, &files)?;
+        if !unlisted.is_empty() {
+            println!(
+                "[警告] 下列檔案不在章節清單 {:?} 中，將不會處理:",
+                order_file
+            );
+            for file in &unlisted {
+                println!("  - {}", file.display());
+            }
+        }
+        files = listed;
+    } else {
+        chapters::sort_naturally(&mut files);
+    }
 
     if files.is_empty() {
         println!("輸入資料夾是空的！");
         return Ok(());
     }
 
+    if let Some(format) = cli.toc {
+        let path = write_toc(&config, &output_names, &files, format)?;
+        println!("已將 {} 章的目錄寫入: {}", files.len(), path.display());
+        return Ok(());
+    }
+
     // 3. 自動偵測建議進度 (Auto-Detect Logic)
     let mut suggested_index = 0;
     for (i, file_path) in files.iter().enumerate() {
-        let file_name = file_path.file_name().unwrap().to_string_lossy();
-        let file_stem = file_path.file_stem().unwrap().to_string_lossy();
-
-        let output_exists = config.translation.output_folder.join(&*file_name).exists();
-        let glossary_exists = config
-            .translation
-            .glossary_folder
-            .join(format!("{}.json", file_stem))
-            .exists();
+        let id = chapters::chapter_id(&config.translation.input_folder, file_path);
+        let glossary = load_glossary(&config.translation.glossary_folder, &id);
+        let status = chapter_status(&config, &output_names, &files, i, glossary.as_ref())?;
 
         // 如果輸出或字典缺一個，就建議從這裡開始
-        if !output_exists || !glossary_exists {
+        if status != ChapterStatus::Translated {
             suggested_index = i;
             break;
         }
@@ -272,6 +1352,46 @@ async fn main() -> Result<()> {
         }
     }
 
+    if cli.dry_run {
+        // 全部完成時改為估算整部作品
+        let start_index = if suggested_index < files.len() {
+            suggested_index
+        } else {
+            0
+        };
+        println!(
+            "=== Dry run：估算第 {} 章到第 {} 章 (不會呼叫 LLM) ===",
+            start_index + 1,
+            files.len()
+        );
+        return dry_run(&config, &prompt_env, &output_names, &files, start_index);
+    }
+
+    let mut llm_client = match &cli.replay {
+        Some(path) => {
+            println!("重播模式：以稽核紀錄 {} 的回應取代 LLM", path.display());
+            Box::new(ReplayClient::load(path)?)
+        }
+        None => {
+            let client = create_llm_client(&config.llm)?;
+            println!("已初始化 LLM Provider: {}", config.llm.provider);
+            client
+        }
+    };
+    if config.cache.enabled && !cli.no_cache && cli.replay.is_none() {
+        llm_client = Box::new(CachedClient::new(
+            llm_client,
+            &config.cache,
+            &config.llm.request_signature(),
+            cli.refresh,
+        ));
+        println!(
+            "已啟用回應快取: {:?}{}",
+            config.cache.folder,
+            if cli.refresh { " (refresh 模式)" } else { "" }
+        );
+    }
+
     // 4. 使用者互動與輸入驗證
     println!("=== AI 翻譯工具啟動 ===");
     println!("共發現 {} 個章節檔案。", files.len());
@@ -322,13 +1442,11 @@ async fn main() -> Result<()> {
     let mut initial_glossary = ChapterGlossary::default();
 
     if start_index > 0 {
-        let prev_file_stem = files[start_index - 1]
-            .file_stem()
-            .unwrap()
-            .to_string_lossy();
-        print!("正在檢查上一章 ({}) 的字典檔... ", prev_file_stem);
+        let prev_id =
+            chapters::chapter_id(&config.translation.input_folder, &files[start_index - 1]);
+        print!("正在檢查上一章 ({}) 的字典檔... ", prev_id);
 
-        if let Some(g) = load_glossary(&config.translation.glossary_folder, &prev_file_stem) {
+        if let Some(g) = load_glossary(&config.translation.glossary_folder, &prev_id) {
             println!("成功載入！ (包含 {} 個詞條)", g.terms.len());
             initial_glossary = g;
         } else {
@@ -352,9 +1470,62 @@ async fn main() -> Result<()> {
 
     // 6. 開始處理迴圈
     let mut current_glossary = initial_glossary;
+    let usage = UsageTracker::new(
+        &config.llm.provider,
+        config.llm.active_model().unwrap_or_default(),
+        config.llm.active_pricing(),
+    );
+    let audit = match &config.runtime.audit_log {
+        Some(path) => {
+            let log = AuditLog::new(path, &config.llm)?;
+            println!("稽核紀錄將寫入: {}", log.path().display());
+            Some(log)
+        }
+        None => None,
+    };
+    let mut session = LlmSession::new(llm_client, usage, audit);
+    let mut daily_ledger = DailyLedger::load(&config.translation.glossary_folder)?;
+    if config.runtime.budget.has_cost_limit() && config.llm.active_pricing().is_none() {
+        println!("[警告] 已設定費用上限，但目前模型沒有 pricing 設定，費用上限不會生效。");
+    }
 
-    for file_path in files.iter().skip(start_index) {
-        match process_chapter(&*llm_client, &config, &prompt_env, file_path, &current_glossary).await {
+    for (index, file_path) in files.iter().enumerate().skip(start_index) {
+        // 預算檢查：達到上限就在開始下一章之前停止
+        if let Some(reason) = config
+            .runtime
+            .budget
+            .exceeded(session.usage.manifest(), &daily_ledger.today())
+        {
+            println!("\n[預算上限] {}，停止執行。", reason);
+            println!(
+                "尚有 {} 章未處理，下次可從第 {} 章 ({}) 繼續。",
+                files.len() - index,
+                index + 1,
+                file_path.file_name().unwrap().to_string_lossy()
+            );
+            break;
+        }
+
+        let result = process_chapter(
+            &mut session,
+            &config,
+            &prompt_env,
+            &output_names,
+            &files,
+            index,
+            &current_glossary,
+        )
+        .await;
+        // 每章結束都更新執行紀錄，避免中斷時遺失用量資料；寫入失敗不影響翻譯進度
+        if let Err(e) = session.usage.save(&config.translation.glossary_folder) {
+            eprintln!("[警告] 寫入執行紀錄失敗: {:?}", e);
+        }
+        daily_ledger.sync(session.usage.manifest());
+        if let Err(e) = daily_ledger.save() {
+            eprintln!("[警告] 寫入每日用量帳本失敗: {:?}", e);
+        }
+
+        match result {
             Ok(new_glossary) => {
                 current_glossary = new_glossary;
             }
@@ -378,14 +1549,211 @@ async fn main() -> Result<()> {
         }
     }
 
+    session.usage.finish();
+    let manifest_path = session.usage.save(&config.translation.glossary_folder)?;
+    let manifest = session.usage.manifest();
     println!("\n工作佇列結束。");
+    println!(
+        "本次執行共 {} 次請求 (快取命中 {} 次)，{}",
+        manifest.requests,
+        manifest.cache_hits,
+        format_usage(&manifest.usage, manifest.estimated_cost)
+    );
+    println!("執行紀錄已寫入: {}", manifest_path.display());
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{is_txt_file, resolve_start_index, sanitize_json_response};
-    use std::path::Path;
+    use super::{
+        AnalysisResponse, ChapterGlossary, ChapterInfo, Character, Config, PreviousTail, Reading,
+        analysis_retry_prompt, build_environment, is_input_file, language_folder_name, notes,
+        parse_analysis_response, render_analysis_prompt, render_translation_prompt,
+        resolve_start_index, sanitize_json_response, validate_prompts,
+    };
+    use crate::characters::Relationship;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn bundled_config_parses() {
+        let config: Config = serde_norway::from_str(include_str!("../config.yml")).unwrap();
+        assert!(config.runtime.budget.max_tokens_per_run.is_none());
+    }
+
+    #[test]
+    fn bundled_prompts_pass_validation() {
+        let config: Config = serde_norway::from_str(include_str!("../config.yml")).unwrap();
+        let env = build_environment(&config.prompts).unwrap();
+        validate_prompts(&config, &env).unwrap();
+    }
+
+    #[test]
+    fn bundled_prompts_render_with_story_memory() {
+        let mut config: Config = serde_norway::from_str(include_str!("../config.yml")).unwrap();
+        config.translation.translator_notes = true;
+        let env = build_environment(&config.prompts).unwrap();
+
+        let mut glossary = ChapterGlossary::default();
+        glossary.memory.record("001", "主角抵達王都");
+        glossary.memory.compress("序章劇情".to_string());
+        glossary.memory.record("011", "主角加入騎士團");
+        glossary.characters.merge(vec![
+            Character {
+                name: "アリス".to_string(),
+                aliases: vec!["姫様".to_string()],
+                first_person_pronoun: "私".to_string(),
+                relationships: vec![Relationship {
+                    character: "ボブ".to_string(),
+                    relation: "主人".to_string(),
+                }],
+                ..Default::default()
+            },
+            Character {
+                name: "魔王".to_string(),
+                ..Default::default()
+            },
+        ]);
+
+        let chapter = ChapterInfo {
+            index: 12,
+            count: 20,
+            name: "012".to_string(),
+            source_lang: "Japanese".to_string(),
+            target_lang: config.translation.target_language.clone(),
+            input_format: "text",
+            readings: vec![Reading {
+                base: "四月一日".to_string(),
+                reading: "わたぬき".to_string(),
+            }],
+        };
+        let analysis = render_analysis_prompt(&config, &env, &chapter, &glossary).unwrap();
+        assert!(analysis.contains("序章劇情"));
+        assert!(analysis.contains("四月一日：わたぬき"));
+        assert!(analysis.contains("[011] 主角加入騎士團"));
+        let translation = render_translation_prompt(
+            &config,
+            &env,
+            &chapter,
+            &glossary,
+            "アリスは王都に着いた。",
+            &PreviousTail::default(),
+        )
+        .unwrap();
+        assert!(translation.contains(&config.translation.target_language));
+        assert!(
+            translation.contains(
+                "- アリス，別名 姫様：性別 未知，語氣 未知，第一人稱 私，關係：ボブ (主人)"
+            )
+        );
+        // 本章沒有出現的角色不放進 prompt
+        assert!(!translation.contains("魔王"));
+        assert!(translation.contains(notes::NOTES_MARKER));
+    }
+
+    #[test]
+    fn custom_variables_and_glossary_list_reach_templates() {
+        let mut config: Config = serde_norway::from_str(include_str!("../config.yml")).unwrap();
+        config.novel.title = "魔女之旅".to_string();
+        config
+            .prompts
+            .variables
+            .insert("honorifics".to_string(), serde_json::json!("保留 -san"));
+        // 同名的自訂變數不會覆蓋內建變數
+        config
+            .prompts
+            .variables
+            .insert("chapter_index".to_string(), serde_json::json!(99));
+        config.prompts.translation_prompt = Some(
+            "{{ novel_title }} {{ chapter_index }}/{{ chapter_count }} {{ honorifics }}\n\
+             {% for entry in glossary_terms %}{{ entry.term }}={{ entry.translation }};{% endfor %}"
+                .to_string(),
+        );
+        let env = build_environment(&config.prompts).unwrap();
+
+        let mut glossary = ChapterGlossary::default();
+        glossary.terms.insert("B".to_string(), "乙".to_string());
+        glossary.terms.insert("A".to_string(), "甲".to_string());
+        let chapter = ChapterInfo {
+            index: 3,
+            count: 10,
+            name: "003".to_string(),
+            source_lang: String::new(),
+            target_lang: config.translation.target_language.clone(),
+            input_format: "text",
+            readings: Vec::new(),
+        };
+        let rendered = render_translation_prompt(
+            &config,
+            &env,
+            &chapter,
+            &glossary,
+            "",
+            &PreviousTail::default(),
+        )
+        .unwrap();
+        assert_eq!(rendered, "魔女之旅 3/10 保留 -san\nA=甲;B=乙;");
+    }
+
+    #[test]
+    fn multiple_target_languages_use_their_own_glossary_column() {
+        let mut config: Config = serde_norway::from_str(include_str!("../config.yml")).unwrap();
+        config.translation.target_language.clear();
+        config.translation.target_languages = vec![
+            "Traditional Chinese (Taiwan)".to_string(),
+            "English".to_string(),
+        ];
+        config.translation.validate().unwrap();
+        assert_eq!(
+            config.translation.output_folder_for("English"),
+            config.translation.output_folder.join("english")
+        );
+        let env = build_environment(&config.prompts).unwrap();
+
+        let mut chapter = ChapterInfo {
+            index: 1,
+            count: 1,
+            name: "001".to_string(),
+            source_lang: "Japanese".to_string(),
+            target_lang: "Traditional Chinese (Taiwan)".to_string(),
+            input_format: "text",
+            readings: Vec::new(),
+        };
+        let mut glossary = ChapterGlossary::default();
+        let analysis = render_analysis_prompt(&config, &env, &chapter, &glossary).unwrap();
+        assert!(analysis.contains("translated_glossaries"));
+
+        let raw = r#"{"summary": "ok", "new_glossary": {"アリス": "愛麗絲"}, "characters": [],
+            "translated_glossaries": [{"language": "English", "new_glossary": [{"term": "アリス", "translation": "Alice"}]}]}"#;
+        let response = parse_analysis_response(raw).unwrap();
+        glossary.terms = response.new_glossary;
+        for column in response.translated_glossaries {
+            glossary
+                .term_columns
+                .insert(column.language, column.new_glossary);
+        }
+
+        chapter.target_lang = "English".to_string();
+        let translation = render_translation_prompt(
+            &config,
+            &env,
+            &chapter,
+            &glossary,
+            "アリス",
+            &PreviousTail::default(),
+        )
+        .unwrap();
+        assert!(translation.contains("Alice"));
+        assert!(!translation.contains("愛麗絲"));
+    }
+
+    #[test]
+    fn language_names_become_folder_names() {
+        assert_eq!(
+            language_folder_name("Traditional Chinese (Taiwan)"),
+            "traditional-chinese-taiwan"
+        );
+        assert_eq!(language_folder_name("简体中文"), "简体中文");
+    }
 
     #[test]
     fn sanitize_json_strips_markdown_fences() {
@@ -399,10 +1767,112 @@ mod tests {
         assert_eq!(sanitize_json_response(raw), "{\"summary\":\"ok\"}");
     }
 
+    #[test]
+    fn analysis_response_is_repaired_when_strict_parse_fails() {
+        let raw =
+            "Here you go:\n{\"summary\": \"他說\"走吧\"\", \"new_glossary\": {\"A\": \"甲\",}}";
+        let analysis = parse_analysis_response(raw).unwrap();
+        assert_eq!(analysis.summary, "他說\"走吧\"");
+        assert_eq!(analysis.new_glossary["A"], "甲");
+    }
+
+    #[test]
+    fn analysis_response_accepts_schema_shaped_glossary() {
+        let raw =
+            r#"{"summary": "ok", "new_glossary": [{"term": "Alice", "translation": "愛麗絲"}]}"#;
+        let analysis = parse_analysis_response(raw).unwrap();
+        assert_eq!(analysis.new_glossary["Alice"], "愛麗絲");
+    }
+
+    #[test]
+    fn analysis_prompt_example_matches_schema() {
+        let mut config: Config = serde_norway::from_str(include_str!("../config.yml")).unwrap();
+        let env = build_environment(&config.prompts).unwrap();
+        let primary = config.translation.target_language.clone();
+        for extra_languages in [vec![], vec!["English"]] {
+            config.translation.target_languages = std::iter::once(primary.as_str())
+                .chain(extra_languages.iter().copied())
+                .map(String::from)
+                .collect();
+            let chapter = ChapterInfo::new(&config.translation, &[PathBuf::from("001.txt")], 0);
+            let prompt =
+                render_analysis_prompt(&config, &env, &chapter, &ChapterGlossary::default())
+                    .unwrap();
+            let (_, example) = prompt.split_once("請回傳標準 JSON 格式：").unwrap();
+            let example: serde_json::Value = serde_json::from_str(example).unwrap();
+            let schema = AnalysisResponse::json_schema(&extra_languages);
+            assert_eq!(validate_schema(&example, &schema), Ok(()));
+            assert!(serde_json::from_value::<AnalysisResponse>(example).is_ok());
+
+            let retry = analysis_retry_prompt("", &schema, &anyhow::anyhow!("x"));
+            for field in schema["required"].as_array().unwrap() {
+                assert!(retry.contains(&format!("\"{}\"", field.as_str().unwrap())));
+            }
+        }
+        // 舊的物件格式不符合 schema
+        let map_shaped = serde_json::json!({
+            "summary": "",
+            "new_glossary": { "魔王": "Demon King" },
+            "characters": []
+        });
+        assert!(validate_schema(&map_shaped, &AnalysisResponse::json_schema(&[])).is_err());
+    }
+
+    // 檢查 schema 中用到的 type / properties / required / additionalProperties / enum / items
+    fn validate_schema(
+        value: &serde_json::Value,
+        schema: &serde_json::Value,
+    ) -> Result<(), String> {
+        let matches_type = match schema["type"].as_str() {
+            Some("object") => value.is_object(),
+            Some("array") => value.is_array(),
+            Some("string") => value.is_string(),
+            _ => true,
+        };
+        if !matches_type {
+            return Err(format!("{} 不是 {}", value, schema["type"]));
+        }
+        if let Some(allowed) = schema["enum"].as_array()
+            && !allowed.contains(value)
+        {
+            return Err(format!("{} 不在 enum 中", value));
+        }
+        if let Some(items) = value.as_array() {
+            for item in items {
+                validate_schema(item, &schema["items"])?;
+            }
+        }
+        if let Some(object) = value.as_object() {
+            for field in schema["required"].as_array().into_iter().flatten() {
+                if !object.contains_key(field.as_str().unwrap()) {
+                    return Err(format!("缺少 {}", field));
+                }
+            }
+            for (key, field) in object {
+                match schema["properties"].get(key) {
+                    Some(property) => validate_schema(field, property)?,
+                    None if schema["additionalProperties"] == false => {
+                        return Err(format!("多出的欄位 {}", key));
+                    }
+                    None => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn analysis_response_must_match_schema() {
+        assert!(parse_analysis_response("{\"summary\": 1}").is_err());
+    }
+
     #[test]
     fn txt_file_detection_is_case_insensitive() {
-        assert!(is_txt_file(Path::new("chapter001.TXT")));
-        assert!(!is_txt_file(Path::new("chapter001.md")));
+        assert!(is_input_file(Path::new("chapter001.TXT")));
+        assert!(is_input_file(Path::new("chapter001.md")));
+        assert!(is_input_file(Path::new("chapter001.HTML")));
+        assert!(is_input_file(Path::new("chapter001.docx")));
+        assert!(!is_input_file(Path::new("chapter001.json")));
     }
 
     #[test]
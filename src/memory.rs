@@ -0,0 +1,153 @@
+// src/memory.rs
+
+use serde::{Deserialize, Serialize};
+
+// 單章摘要
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ChapterSummary {
+    pub chapter: String,
+    pub summary: String,
+}
+
+// 多章壓縮後的劇情段落
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ArcSummary {
+    pub first_chapter: String,
+    pub last_chapter: String,
+    pub summary: String,
+}
+
+/// 分層的劇情記憶：較早的劇情壓縮成 arc，最近幾章保留逐章摘要
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct StoryMemory {
+    #[serde(default)]
+    pub arcs: Vec<ArcSummary>,
+    #[serde(default)]
+    pub recent: Vec<ChapterSummary>, // 尚未壓縮的逐章摘要
+}
+
+impl StoryMemory {
+    pub fn record(&mut self, chapter: &str, summary: &str) {
+        self.recent.push(ChapterSummary {
+            chapter: chapter.to_string(),
+            summary: summary.to_string(),
+        });
+    }
+
+    /// interval 為 0 表示不壓縮
+    pub fn needs_compression(&self, interval: usize) -> bool {
+        interval > 0 && self.recent.len() >= interval
+    }
+
+    /// 不壓縮時只保留最近 keep 章的摘要
+    pub fn trim_recent(&mut self, keep: usize) {
+        let excess = self.recent.len().saturating_sub(keep);
+        self.recent.drain(..excess);
+    }
+
+    /// 給壓縮用的 LLM 輸入：逐章摘要
+    pub fn compression_input(&self) -> String {
+        self.recent
+            .iter()
+            .map(|c| format!("[{}] {}", c.chapter, c.summary))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// 以壓縮結果取代目前的逐章摘要
+    pub fn compress(&mut self, summary: String) {
+        let (Some(first), Some(last)) = (self.recent.first(), self.recent.last()) else {
+            return;
+        };
+        self.arcs.push(ArcSummary {
+            first_chapter: first.chapter.clone(),
+            last_chapter: last.chapter.clone(),
+            summary,
+        });
+        self.recent.clear();
+    }
+
+    /// max 為 0 表示不限制 arc 數量
+    pub fn too_many_arcs(&self, max: usize) -> bool {
+        max > 0 && self.arcs.len() > max
+    }
+
+    /// 將所有 arc 合併成一段，避免 story_so_far 隨章數無限增長
+    pub fn merge_arcs(&mut self, summary: String) {
+        let (Some(first), Some(last)) = (self.arcs.first(), self.arcs.last()) else {
+            return;
+        };
+        let merged = ArcSummary {
+            first_chapter: first.first_chapter.clone(),
+            last_chapter: last.last_chapter.clone(),
+            summary,
+        };
+        self.arcs = vec![merged];
+    }
+
+    pub fn story_so_far(&self) -> String {
+        self.arcs
+            .iter()
+            .map(|arc| {
+                format!(
+                    "[{} - {}] {}",
+                    arc.first_chapter, arc.last_chapter, arc.summary
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StoryMemory;
+
+    #[test]
+    fn compression_moves_recent_summaries_into_an_arc() {
+        let mut memory = StoryMemory::default();
+        memory.record("001", "相遇");
+        assert!(!memory.needs_compression(2));
+        memory.record("002", "結盟");
+        assert!(memory.needs_compression(2));
+        assert_eq!(memory.compression_input(), "[001] 相遇\n[002] 結盟");
+
+        memory.compress("兩人相遇並結盟".to_string());
+        assert!(memory.recent.is_empty());
+        assert_eq!(memory.story_so_far(), "[001 - 002] 兩人相遇並結盟");
+    }
+
+    #[test]
+    fn arcs_over_the_limit_are_merged() {
+        let mut memory = StoryMemory::default();
+        for (first, last) in [("001", "010"), ("011", "020"), ("021", "030")] {
+            memory.record(first, "x");
+            memory.record(last, "y");
+            memory.compress(format!("{}-{}", first, last));
+        }
+        assert!(memory.too_many_arcs(2));
+        assert!(!memory.too_many_arcs(3));
+        assert!(!memory.too_many_arcs(0));
+
+        memory.merge_arcs("前三十章".to_string());
+        assert_eq!(memory.story_so_far(), "[001 - 030] 前三十章");
+    }
+
+    #[test]
+    fn zero_interval_disables_compression() {
+        let mut memory = StoryMemory::default();
+        memory.record("001", "相遇");
+        assert!(!memory.needs_compression(0));
+    }
+
+    #[test]
+    fn trim_keeps_latest_summaries() {
+        let mut memory = StoryMemory::default();
+        for chapter in ["001", "002", "003"] {
+            memory.record(chapter, "x");
+        }
+        memory.trim_recent(2);
+        assert_eq!(memory.recent[0].chapter, "002");
+        assert_eq!(memory.recent.len(), 2);
+    }
+}
@@ -0,0 +1,307 @@
+// src/notes.rs
+
+use anyhow::Result;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::LazyLock;
+
+use crate::encoding::EncodingConfig;
+use crate::json_repair::repair_json_object;
+use crate::language::{ChineseScript, Language};
+
+/// Pass 2 回應中譯文與譯註 JSON 之間的分隔行
+pub const NOTES_MARKER: &str = "[[TRANSLATOR_NOTES]]";
+
+/// 目標語言預設的譯註區塊標題 (可用 translation.notes_headings 覆寫)
+pub fn default_heading(target: &str) -> &'static str {
+    match Language::from_name(target) {
+        Some(Language::Chinese)
+            if ChineseScript::from_name(target) == Some(ChineseScript::Simplified) =>
+        {
+            "译注"
+        }
+        Some(Language::Japanese) => "訳注",
+        Some(Language::Korean) => "역주",
+        Some(Language::English) => "Translator's Notes",
+        Some(Language::Russian) => "Примечания переводчика",
+        Some(Language::Chinese) | None => "譯註",
+    }
+}
+
+// Markdown 註腳定義 ([^1]: ...) 與標記 ([^1])、HTML 的譯註標記
+static FOOTNOTE_DEFINITION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^\[\^\d+\]:.*(?:\n|$)").unwrap());
+static FOOTNOTE_MARKER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"\[\^\d+\]|<sup class="translator-note-ref">.*?</sup>"#).unwrap()
+});
+// .txt 與 Word 的 [1] 標記，只在有譯註區塊時移除
+static NOTE_MARKER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\[\d+\]").unwrap());
+
+/// 譯註的輸出方式
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum NotesOutput {
+    #[default]
+    Inline, // 加在譯文中：.txt 為文末註、Markdown/HTML 為註腳
+    File, // 譯文不加標記，另存 <章名>.notes.json
+}
+
+/// 譯註：paragraph 為譯文段落編號 (從 1 開始，0 表示不指定)，anchor 為要加註的譯文詞句
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Note {
+    #[serde(default)]
+    pub paragraph: usize,
+    #[serde(default)]
+    pub anchor: String,
+    pub note: String,
+}
+
+impl Note {
+    /// 不對應任何位置的文末註
+    pub fn endnote(note: String) -> Self {
+        Self {
+            paragraph: 0,
+            anchor: String::new(),
+            note,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct NotesBlock {
+    #[serde(default)]
+    notes: Vec<Note>,
+}
+
+/// 編號後的譯註；anchored 表示譯文中有對應的標記
+#[derive(Debug, Serialize, PartialEq)]
+pub struct PlacedNote {
+    pub number: usize,
+    pub anchored: bool,
+    #[serde(flatten)]
+    pub note: Note,
+}
+
+fn parse_notes(raw: &str) -> Option<Vec<Note>> {
+    let raw = raw
+        .trim()
+        .trim_start_matches("```json")
+        .trim_matches('`')
+        .trim();
+    if let Ok(notes) = serde_json::from_str::<Vec<Note>>(raw) {
+        return Some(notes);
+    }
+    let json = repair_json_object(raw)?;
+    serde_json::from_str::<NotesBlock>(&json)
+        .ok()
+        .map(|block| block.notes)
+}
+
+/// 將 Pass 2 的回應拆成譯文與譯註；沒有分隔行時譯註為空
+pub fn split_response(response: &str) -> (String, Vec<Note>) {
+    let Some(at) = response.find(NOTES_MARKER) else {
+        return (response.to_string(), Vec::new());
+    };
+    let translation = response[..at].trim_end().to_string() + "\n";
+    let notes = parse_notes(&response[at + NOTES_MARKER.len()..]).unwrap_or_else(|| {
+        println!("    [警告] 無法解析譯註 JSON，已略過");
+        Vec::new()
+    });
+    let notes = notes
+        .into_iter()
+        .filter(|n| !n.note.trim().is_empty())
+        .collect();
+    (translation, notes)
+}
+
+// 譯註在譯文中的位置：(行號, 行內位元組位置)
+fn locate(lines: &[&str], paragraphs: &[usize], note: &Note) -> Option<(usize, usize)> {
+    let anchor = note.anchor.trim();
+    let find = |line: usize| {
+        if anchor.is_empty() {
+            return None;
+        }
+        lines[line].find(anchor).map(|at| (line, at + anchor.len()))
+    };
+    let line = note
+        .paragraph
+        .checked_sub(1)
+        .and_then(|i| paragraphs.get(i).copied());
+    if let Some(position) = line.and_then(find) {
+        return Some(position);
+    }
+    // 段落編號錯誤時在全文中找 anchor，找不到則放在指定段落的結尾
+    paragraphs
+        .iter()
+        .find_map(|&line| find(line))
+        .or_else(|| line.map(|line| (line, lines[line].trim_end().len())))
+}
+
+/// 依譯註位置在譯文中插入 marker(編號)，並依出現順序編號；沒有位置的譯註排在最後
+pub fn place(
+    translation: &str,
+    notes: &[Note],
+    marker: impl Fn(usize) -> String,
+) -> (String, Vec<PlacedNote>) {
+    let lines: Vec<&str> = translation.split('\n').collect();
+    let paragraphs: Vec<usize> = (0..lines.len())
+        .filter(|&i| !lines[i].trim().is_empty())
+        .collect();
+    let mut located: Vec<(Option<(usize, usize)>, &Note)> = notes
+        .iter()
+        .map(|note| (locate(&lines, &paragraphs, note), note))
+        .collect();
+    located.sort_by_key(|(position, _)| position.unwrap_or((usize::MAX, 0)));
+
+    let mut inserts: Vec<Vec<(usize, String)>> = vec![Vec::new(); lines.len()];
+    let placed = located
+        .into_iter()
+        .enumerate()
+        .map(|(i, (position, note))| {
+            if let Some((line, at)) = position {
+                inserts[line].push((at, marker(i + 1)));
+            }
+            PlacedNote {
+                number: i + 1,
+                anchored: position.is_some(),
+                note: note.clone(),
+            }
+        })
+        .collect();
+
+    let marked: Vec<String> = lines
+        .iter()
+        .zip(inserts)
+        .map(|(line, inserts)| {
+            let mut line = line.to_string();
+            for (at, marker) in inserts.into_iter().rev() {
+                line.insert_str(at, &marker);
+            }
+            line
+        })
+        .collect();
+    (marked.join("\n"), placed)
+}
+
+/// 去掉譯文中的譯註：以 heading 開頭的譯註區塊、Markdown 註腳與譯註標記 (讀取上一章譯文結尾與計算字數時使用)
+pub fn strip_endnotes(text: &str, heading: &str) -> String {
+    let mut offset = 0;
+    let mut cut = None;
+    for line in text.split_inclusive('\n') {
+        let title = line.trim().trim_start_matches('#').trim();
+        if title == heading {
+            cut = Some(offset);
+        }
+        offset += line.len();
+    }
+    let body = &text[..cut.unwrap_or(text.len())];
+    let body = FOOTNOTE_DEFINITION.replace_all(body, "");
+    let body = FOOTNOTE_MARKER.replace_all(&body, "");
+    match cut {
+        Some(_) => NOTE_MARKER.replace_all(&body, "").into_owned(),
+        None => body.into_owned(),
+    }
+}
+
+/// notes_output: file 時另存的譯註檔
+pub fn write_file(
+    path: &Path,
+    chapter: &str,
+    notes: &[Note],
+    encoding: &EncodingConfig,
+) -> Result<()> {
+    let (_, placed) = place("", notes, |_| String::new());
+    let json = serde_json::json!({ "chapter": chapter, "notes": placed });
+    encoding.write_output(path, &serde_json::to_string_pretty(&json)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Note, default_heading, place, split_response, strip_endnotes};
+
+    fn note(paragraph: usize, anchor: &str, text: &str) -> Note {
+        Note {
+            paragraph,
+            anchor: anchor.to_string(),
+            note: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn notes_block_is_split_from_the_translation() {
+        let response = "第一章\n\n\n他說了冷笑話。\n[[TRANSLATOR_NOTES]]\n```json\n{\"notes\": [{\"paragraph\": 2, \"anchor\": \"冷笑話\", \"note\": \"原文為雙關語\"},]}\n```";
+        let (translation, notes) = split_response(response);
+        assert_eq!(translation, "第一章\n\n\n他說了冷笑話。\n");
+        assert_eq!(notes, [note(2, "冷笑話", "原文為雙關語")]);
+
+        let (translation, notes) = split_response("沒有譯註");
+        assert_eq!(translation, "沒有譯註");
+        assert!(notes.is_empty());
+    }
+
+    #[test]
+    fn markers_follow_anchors_and_are_numbered_in_order() {
+        let translation = "第一章\n\n甲說了冷笑話。\n乙吃了年糕。";
+        let notes = [
+            note(3, "年糕", "新年的食物"),
+            note(0, "", "讀音註"),
+            note(9, "冷笑話", "雙關語"),
+            note(2, "不存在", "放在段落結尾"),
+        ];
+        let (marked, placed) = place(translation, &notes, |n| format!("[{}]", n));
+        assert_eq!(marked, "第一章\n\n甲說了冷笑話[1]。[2]\n乙吃了年糕[3]。");
+        let order: Vec<_> = placed
+            .iter()
+            .map(|p| (p.number, p.anchored, p.note.note.as_str()))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                (1, true, "雙關語"),
+                (2, true, "放在段落結尾"),
+                (3, true, "新年的食物"),
+                (4, false, "讀音註")
+            ]
+        );
+    }
+
+    #[test]
+    fn endnotes_are_removed_from_previous_output() {
+        assert_eq!(strip_endnotes("正文\n\n譯註\n[1] 註\n", "譯註"), "正文\n\n");
+        assert_eq!(
+            strip_endnotes("# 標題\n\n## 譯註\n\n- 註\n", "譯註"),
+            "# 標題\n\n"
+        );
+        assert_eq!(strip_endnotes("正文", "譯註"), "正文");
+        assert_eq!(
+            strip_endnotes("他說了冷笑話[1]。\n\n譯註\n[1] 註\n", "譯註"),
+            "他說了冷笑話。\n\n"
+        );
+        // 全部譯註都有標記時 Markdown 只有註腳定義，沒有譯註標題
+        assert_eq!(
+            strip_endnotes("# 標題\n\n他吃了年糕[^1]。\n\n[^1]: 新年的食物\n", "譯註"),
+            "# 標題\n\n他吃了年糕。\n\n"
+        );
+        assert_eq!(strip_endnotes("第[1]卷", "譯註"), "第[1]卷");
+    }
+
+    #[test]
+    fn headings_follow_the_target_language() {
+        assert_eq!(default_heading("Traditional Chinese (Taiwan)"), "譯註");
+        assert_eq!(default_heading("Simplified Chinese"), "译注");
+        assert_eq!(default_heading("English"), "Translator's Notes");
+        assert_eq!(default_heading("日本語"), "訳注");
+        assert_eq!(
+            strip_endnotes(
+                "He told a pun[1].\n\n## Translator's Notes\n\n- 註\n",
+                "Translator's Notes"
+            ),
+            "He told a pun.\n\n"
+        );
+        assert_eq!(
+            strip_endnotes("正文\n\n譯註\n", "Translator's Notes"),
+            "正文\n\n譯註\n"
+        );
+    }
+}
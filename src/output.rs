@@ -0,0 +1,174 @@
+// src/output.rs
+
+use anyhow::{Context, Result, bail};
+use minijinja::{Environment, UndefinedBehavior, Value, context};
+use regex::Regex;
+use std::path::{Component, Path, PathBuf};
+use std::sync::LazyLock;
+
+const TEMPLATE: &str = "output_filename";
+
+/// 未設定 output_filename 時沿用原檔名
+pub const DEFAULT_TEMPLATE: &str = "{{ name }}.{{ ext }}";
+
+// 檔名不能使用的字元
+static INVALID_CHARS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"[<>:"|?*\x00-\x1f]"#).unwrap());
+static HTML_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]*>").unwrap());
+
+// 標題用於檔名時的最大字數
+const MAX_TITLE_CHARS: usize = 80;
+
+// pad 過濾器：左側補 0 到指定寬度，例如 {{ index | pad(4) }} -> 0007
+fn pad(value: Value, width: usize) -> String {
+    format!("{:0>width$}", value.to_string(), width = width)
+}
+
+/// 去掉 HTML 標籤 (含 Word 段落的 <sN> 樣式標記)
+pub fn strip_tags(text: &str) -> String {
+    HTML_TAG.replace_all(text, "").into_owned()
+}
+
+/// 譯文的第一個非空白行 (章節名稱)，去掉 Markdown 標題符號與 HTML 標籤
+pub fn first_line_title(translation: &str) -> String {
+    translation
+        .lines()
+        .map(|line| strip_tags(line).trim().to_string())
+        .map(|line| line.trim_start_matches('#').trim().to_string())
+        .find(|line| !line.is_empty())
+        .unwrap_or_default()
+}
+
+// 去掉檔名不能使用的字元並限制長度
+fn sanitize(component: &str) -> String {
+    let cleaned = INVALID_CHARS.replace_all(component, "");
+    let cleaned: String = cleaned.trim().chars().take(MAX_TITLE_CHARS).collect();
+    cleaned.trim().trim_end_matches('.').to_string()
+}
+
+/// 依 translation.output_filename 模板產生譯文的檔名
+pub struct OutputNames {
+    env: Environment<'static>,
+}
+
+impl OutputNames {
+    pub fn new(template: Option<&str>) -> Result<Self> {
+        let mut env = Environment::new();
+        env.set_undefined_behavior(UndefinedBehavior::Strict);
+        env.add_filter("pad", pad);
+        env.add_template_owned(TEMPLATE, template.unwrap_or(DEFAULT_TEMPLATE).to_string())
+            .context("output_filename 模板錯誤")?;
+        let names = Self { env };
+        // 啟動時先渲染一次，提早發現錯誤
+        names
+            .relative_path(Path::new("."), Path::new("./001.txt"), 1, 1, "", "")
+            .context("output_filename 模板錯誤")?;
+        Ok(names)
+    }
+
+    /// 相對於輸出資料夾的路徑：保留章節在 input_folder 中的子資料夾，檔名由模板決定
+    /// translated_title 為空時，模板中的 translated_title 改用原檔名
+    pub fn relative_path(
+        &self,
+        input_folder: &Path,
+        file: &Path,
+        index: usize,
+        count: usize,
+        target_lang: &str,
+        translated_title: &str,
+    ) -> Result<PathBuf> {
+        let name = file.file_stem().unwrap_or_default().to_string_lossy();
+        let ext = file.extension().unwrap_or_default().to_string_lossy();
+        // 標題中的 / 不是子資料夾
+        let title = match sanitize(&translated_title.replace(['/', '\\'], "")) {
+            title if title.is_empty() => name.to_string(),
+            title => title,
+        };
+        let rendered = self.env.get_template(TEMPLATE)?.render(context! {
+            index => index,
+            count => count,
+            name => name,
+            ext => ext,
+            target_lang => target_lang,
+            translated_title => title,
+        })?;
+
+        // 模板可以用 / 建立子資料夾，但不能跳出輸出資料夾
+        let file_name: PathBuf = rendered
+            .split(['/', '\\'])
+            .map(sanitize)
+            .filter(|part| !part.is_empty() && part != "." && part != "..")
+            .collect();
+        if file_name.as_os_str().is_empty() {
+            bail!("output_filename 模板產生了空的檔名: {:?}", rendered);
+        }
+        let folder: PathBuf = file
+            .parent()
+            .and_then(|parent| parent.strip_prefix(input_folder).ok())
+            .map(|dir| {
+                dir.components()
+                    .filter(|c| matches!(c, Component::Normal(_)))
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(folder.join(file_name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OutputNames, first_line_title};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn default_template_keeps_name_and_subdirectory() {
+        let names = OutputNames::new(None).unwrap();
+        let path = names
+            .relative_path(
+                Path::new("in"),
+                Path::new("in/vol2/003.md"),
+                3,
+                9,
+                "English",
+                "",
+            )
+            .unwrap();
+        assert_eq!(path, PathBuf::from("vol2/003.md"));
+    }
+
+    #[test]
+    fn template_uses_padding_and_translated_title() {
+        let names = OutputNames::new(Some(
+            "{{ index | pad(4) }}_{{ translated_title }}.{{ ext }}",
+        ))
+        .unwrap();
+        let path = names
+            .relative_path(
+                Path::new("in"),
+                Path::new("in/003.txt"),
+                3,
+                9,
+                "",
+                "第三話：旅/立ち?",
+            )
+            .unwrap();
+        assert_eq!(path, PathBuf::from("0003_第三話：旅立ち.txt"));
+        // 沒有譯名時使用原檔名
+        let path = names
+            .relative_path(Path::new("in"), Path::new("in/003.txt"), 3, 9, "", "")
+            .unwrap();
+        assert_eq!(path, PathBuf::from("0003_003.txt"));
+        assert!(OutputNames::new(Some("{{ missing }}.txt")).is_err());
+        let escape = OutputNames::new(Some("../../{{ name }}.txt")).unwrap();
+        let path = escape
+            .relative_path(Path::new("in"), Path::new("in/003.txt"), 3, 9, "", "")
+            .unwrap();
+        assert_eq!(path, PathBuf::from("003.txt"));
+    }
+
+    #[test]
+    fn title_comes_from_first_nonempty_line() {
+        assert_eq!(first_line_title("\n\n第一章 出發\n\n\n正文"), "第一章 出發");
+        assert_eq!(first_line_title("# <em>序章</em>\n"), "序章");
+    }
+}
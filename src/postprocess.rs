@@ -0,0 +1,377 @@
+// src/postprocess.rs
+
+use crate::language::Language;
+use anyhow::{Context, Result, bail};
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use std::sync::LazyLock;
+
+fn default_max_blank_lines() -> usize {
+    2
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum QuoteStyle {
+    Corner, // 「」『』
+    Curly,  // “”‘’
+}
+
+/// postprocess 區塊的一個步驟，依設定順序套用在 Pass 2 的譯文上
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Step {
+    /// 去掉開頭的「以下是翻譯：」等說明行與包住譯文的 ``` 區塊
+    StripPreamble,
+    /// 正規表示式取代，replacement 可使用 $1 等群組
+    Regex {
+        pattern: String,
+        #[serde(default)]
+        replacement: String,
+    },
+    /// 統一引號樣式
+    Quotes { style: QuoteStyle },
+    /// 台灣中文標點：中文後的半形標點改為全形、刪節號改為……、全形英數字改為半形
+    Punctuation,
+    /// 去掉行尾空白，連續空行最多保留 max 行
+    BlankLines {
+        #[serde(default = "default_max_blank_lines")]
+        max: usize,
+    },
+}
+
+/// postprocess 清單中的一項：步驟與適用的目標語言
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct Rule {
+    #[serde(flatten)]
+    pub step: Step,
+    /// 只套用在這些目標語言 (與 translation 中的名稱相同)；
+    /// 未設定時 punctuation 只用於中文、quotes corner 只用於中文與日文，其餘步驟用於所有語言
+    #[serde(default)]
+    pub targets: Vec<String>,
+}
+
+impl Rule {
+    fn applies_to(&self, target: &str) -> bool {
+        if !self.targets.is_empty() {
+            return self
+                .targets
+                .iter()
+                .any(|name| name.trim().eq_ignore_ascii_case(target.trim()));
+        }
+        // 認不出的語言名稱無法判斷，照設定套用
+        let Some(language) = Language::from_name(target) else {
+            return true;
+        };
+        match self.step {
+            Step::Punctuation => language == Language::Chinese,
+            Step::Quotes {
+                style: QuoteStyle::Corner,
+            } => matches!(language, Language::Chinese | Language::Japanese),
+            _ => true,
+        }
+    }
+}
+
+static PREAMBLE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(concat!(
+        r"(?i)^\s*(?:以下(?:是|為)|下面是|這是|这是|以下|here\s+is|here's|sure[,!.]?\s*here\s+is)",
+        // 必須以冒號結尾，避免把「這是翻譯家的故事」之類的章節名稱當成說明行
+        r"[^\n]{0,30}?(?:翻譯|翻译|譯文|译文|translation)[^\n]{0,20}?[:：]\s*$",
+    ))
+    .unwrap()
+});
+static FENCE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*```[\w-]*[ \t]*\n([\s\S]*?)\n?```\s*$").unwrap());
+// 譯文中的 HTML 標籤與 Word 樣式標記，引號與標點不轉換
+static TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"</?[A-Za-z][^<>\n]*>").unwrap());
+
+static ELLIPSIS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\.{3,}|。{3,}|…+|⋯+").unwrap());
+static HALF_WIDTH: LazyLock<Regex> = LazyLock::new(|| {
+    // 句點後面必須是空白或行尾，避免改到 1.5 或網址
+    Regex::new(concat!(
+        r"(?m)([\p{Han}\p{Hiragana}\p{Katakana}」』）…])[ \t]*",
+        r"(?:([,?!:;])[ \t]*|(\.)(?:[ \t]+|$))",
+    ))
+    .unwrap()
+});
+static PARENTHESES: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\(([^()\n]*\p{Han}[^()\n]*)\)").unwrap());
+
+/// 啟動時檢查設定，提早發現錯誤的正規表示式與不存在的目標語言
+pub fn validate(rules: &[Rule], targets: &[&str]) -> Result<()> {
+    for rule in rules {
+        if let Some(name) = rule.targets.iter().find(|name| {
+            !targets
+                .iter()
+                .any(|target| name.trim().eq_ignore_ascii_case(target.trim()))
+        }) {
+            bail!(
+                "postprocess 的 targets 中有不在翻譯目標內的語言: {} (目標: {})",
+                name,
+                targets.join(", ")
+            );
+        }
+        apply_step(&rule.step, "")?;
+    }
+    Ok(())
+}
+
+/// 依序套用適用於目標語言的步驟
+pub fn apply(rules: &[Rule], target: &str, text: &str) -> Result<String> {
+    let mut text = text.to_string();
+    for rule in rules.iter().filter(|rule| rule.applies_to(target)) {
+        text = apply_step(&rule.step, &text)?;
+    }
+    Ok(text)
+}
+
+fn apply_step(step: &Step, text: &str) -> Result<String> {
+    Ok(match step {
+        Step::StripPreamble => strip_preamble(text),
+        Step::Regex {
+            pattern,
+            replacement,
+        } => {
+            let regex = Regex::new(pattern)
+                .with_context(|| format!("postprocess 正規表示式錯誤: {}", pattern))?;
+            regex.replace_all(text, replacement.as_str()).into_owned()
+        }
+        Step::Quotes { style } => convert_quotes(text, *style),
+        Step::Punctuation => normalize_punctuation(text),
+        Step::BlankLines { max } => normalize_blank_lines(text, *max),
+    })
+}
+
+// 只轉換標籤以外的文字
+fn outside_tags(text: &str, convert: impl Fn(&str) -> String) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+    for tag in TAG.find_iter(text) {
+        result.push_str(&convert(&text[last..tag.start()]));
+        result.push_str(tag.as_str());
+        last = tag.end();
+    }
+    result.push_str(&convert(&text[last..]));
+    result
+}
+
+pub fn strip_preamble(text: &str) -> String {
+    let mut text = text.trim_start();
+    // 說明行可能在 ``` 之前或之內，最多去掉兩次
+    for _ in 0..2 {
+        if let Some(end) = text.find('\n')
+            && PREAMBLE.is_match(&text[..end])
+        {
+            text = text[end + 1..].trim_start();
+        }
+        if let Some(caps) = FENCE.captures(text) {
+            text = caps.get(1).unwrap().as_str().trim_start();
+        }
+    }
+    text.to_string()
+}
+
+pub fn convert_quotes(text: &str, style: QuoteStyle) -> String {
+    outside_tags(text, |segment| {
+        let chars: Vec<char> = segment.chars().collect();
+        let mut open = true; // 直引號 " 依出現順序交替開合
+        let mut result = String::with_capacity(segment.len());
+        for (i, &c) in chars.iter().enumerate() {
+            let converted = match (style, c) {
+                (QuoteStyle::Corner, '“') => '「',
+                (QuoteStyle::Corner, '”') => '」',
+                (QuoteStyle::Corner, '‘') => '『',
+                // 英文單字中的 ’ 是縮寫符號
+                (QuoteStyle::Corner, '’') => {
+                    let letter = |at: Option<&char>| at.is_some_and(|c| c.is_ascii_alphanumeric());
+                    if letter(i.checked_sub(1).and_then(|p| chars.get(p)))
+                        && letter(chars.get(i + 1))
+                    {
+                        '’'
+                    } else {
+                        '』'
+                    }
+                }
+                (QuoteStyle::Corner, '"') => {
+                    open = !open;
+                    if open { '」' } else { '「' }
+                }
+                (QuoteStyle::Curly, '「') => '“',
+                (QuoteStyle::Curly, '」') => '”',
+                (QuoteStyle::Curly, '『') => '‘',
+                (QuoteStyle::Curly, '』') => '’',
+                (QuoteStyle::Curly, '"') => {
+                    open = !open;
+                    if open { '”' } else { '“' }
+                }
+                _ => c,
+            };
+            if c == '\n' {
+                open = true;
+            }
+            result.push(converted);
+        }
+        result
+    })
+}
+
+fn full_width_punctuation(c: &str) -> &'static str {
+    match c {
+        "," => "，",
+        "?" => "？",
+        "!" => "！",
+        ":" => "：",
+        ";" => "；",
+        _ => "。",
+    }
+}
+
+pub fn normalize_punctuation(text: &str) -> String {
+    outside_tags(text, |segment| {
+        // 全形英數字改為半形
+        let segment: String = segment
+            .chars()
+            .map(|c| match c {
+                '０'..='９' | 'Ａ'..='Ｚ' | 'ａ'..='ｚ' => {
+                    char::from_u32(c as u32 - 0xFEE0).unwrap_or(c)
+                }
+                _ => c,
+            })
+            .collect();
+        let segment = ELLIPSIS.replace_all(&segment, "……");
+        let segment = HALF_WIDTH.replace_all(&segment, |caps: &Captures| {
+            let mark = caps.get(2).or(caps.get(3)).unwrap().as_str();
+            format!("{}{}", &caps[1], full_width_punctuation(mark))
+        });
+        PARENTHESES.replace_all(&segment, "（$1）").into_owned()
+    })
+}
+
+pub fn normalize_blank_lines(text: &str, max: usize) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    let mut blank = 0;
+    for line in text.lines().map(str::trim_end) {
+        if line.is_empty() {
+            blank += 1;
+            if blank > max || lines.is_empty() {
+                continue;
+            }
+        } else {
+            blank = 0;
+        }
+        lines.push(line);
+    }
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        QuoteStyle, Rule, Step, apply, convert_quotes, normalize_blank_lines,
+        normalize_punctuation, strip_preamble, validate,
+    };
+
+    #[test]
+    fn preamble_and_fences_are_removed() {
+        assert_eq!(
+            strip_preamble("以下是翻譯：\n\n第一章\n\n正文"),
+            "第一章\n\n正文"
+        );
+        assert_eq!(
+            strip_preamble("Here is the translation:\n```text\n第一章\n正文\n```\n"),
+            "第一章\n正文"
+        );
+        assert_eq!(strip_preamble("```\n以下為譯文：\n第一章\n```"), "第一章");
+        assert_eq!(strip_preamble("以下省略\n正文"), "以下省略\n正文");
+        assert_eq!(
+            strip_preamble("這是翻譯家的故事\n\n正文"),
+            "這是翻譯家的故事\n\n正文"
+        );
+    }
+
+    #[test]
+    fn quotes_are_converted_outside_tags() {
+        assert_eq!(
+            convert_quotes("“走吧，”她說，‘真的’。\n\"好\"與\"壞\"", QuoteStyle::Corner),
+            "「走吧，」她說，『真的』。\n「好」與「壞」"
+        );
+        assert_eq!(
+            convert_quotes("<a href=\"x\">don’t “go”</a>", QuoteStyle::Corner),
+            "<a href=\"x\">don’t 「go」</a>"
+        );
+        assert_eq!(
+            convert_quotes("「他說『好』」", QuoteStyle::Curly),
+            "“他說‘好’”"
+        );
+    }
+
+    #[test]
+    fn taiwan_punctuation_is_normalized() {
+        assert_eq!(
+            normalize_punctuation("他說, 等一下...真的?好!\n第３章(回憶)。Version 1.5, OK."),
+            "他說，等一下……真的？好！\n第3章（回憶）。Version 1.5, OK."
+        );
+        assert_eq!(normalize_punctuation("<s1>是嗎.</s1>"), "<s1>是嗎。</s1>");
+    }
+
+    #[test]
+    fn blank_lines_are_collapsed_and_trailing_spaces_removed() {
+        assert_eq!(
+            normalize_blank_lines("\n第一章  \n\n\n\n正文 \n\n\n", 2),
+            "第一章\n\n\n正文\n"
+        );
+        assert_eq!(normalize_blank_lines("一\n\n\n二", 0), "一\n二\n");
+    }
+
+    fn rule(step: Step) -> Rule {
+        Rule {
+            step,
+            targets: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn steps_run_in_order_and_regex_errors_are_reported() {
+        let rules = [
+            rule(Step::StripPreamble),
+            rule(Step::Regex {
+                pattern: "魔王".to_string(),
+                replacement: "魔王大人".to_string(),
+            }),
+            rule(Step::BlankLines { max: 1 }),
+        ];
+        assert_eq!(
+            apply(&rules, "繁體中文", "以下是翻譯：\n魔王來了。  \n\n\n").unwrap(),
+            "魔王大人來了。\n"
+        );
+        let broken = [rule(Step::Regex {
+            pattern: "(".to_string(),
+            replacement: String::new(),
+        })];
+        assert!(validate(&broken, &["繁體中文"]).is_err());
+    }
+
+    #[test]
+    fn steps_follow_the_target_language() {
+        let rules: Vec<Rule> = serde_norway::from_str(
+            "- type: punctuation\n\
+             - type: quotes\n  style: corner\n\
+             - type: regex\n  pattern: 'Mr\\.'\n  replacement: Mister\n  targets: [English]\n",
+        )
+        .unwrap();
+        assert_eq!(rules[2].targets, ["English"]);
+        let text = "他說:\"Mr. 王\"";
+        assert_eq!(apply(&rules, "繁體中文", text).unwrap(), "他說：「Mr. 王」");
+        assert_eq!(
+            apply(&rules, "English", text).unwrap(),
+            "他說:\"Mister 王\""
+        );
+        assert_eq!(apply(&rules, "日本語", text).unwrap(), "他說:「Mr. 王」");
+        assert!(validate(&rules, &["繁體中文", "English"]).is_ok());
+        assert!(validate(&rules, &["繁體中文"]).is_err());
+    }
+}
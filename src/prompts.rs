@@ -0,0 +1,248 @@
+// src/prompts.rs
+
+use anyhow::{Result, bail};
+use minijinja::{Environment, UndefinedBehavior};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// 程式使用的進入點模板
+pub const ENTRY_TEMPLATES: [&str; 3] = ["analysis", "translation", "story_arc"];
+
+// 內建模板 (以 include/extends 路徑為名)
+const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
+    (
+        "base/analysis.j2",
+        include_str!("../prompts/base/analysis.j2"),
+    ),
+    (
+        "base/translation.j2",
+        include_str!("../prompts/base/translation.j2"),
+    ),
+    (
+        "base/story_arc.j2",
+        include_str!("../prompts/base/story_arc.j2"),
+    ),
+    (
+        "base/_characters.j2",
+        include_str!("../prompts/base/_characters.j2"),
+    ),
+    (
+        "presets/ja-zh-TW/analysis.j2",
+        include_str!("../prompts/presets/ja-zh-TW/analysis.j2"),
+    ),
+    (
+        "presets/ja-zh-TW/translation.j2",
+        include_str!("../prompts/presets/ja-zh-TW/translation.j2"),
+    ),
+    (
+        "presets/ko-zh-TW/analysis.j2",
+        include_str!("../prompts/presets/ko-zh-TW/analysis.j2"),
+    ),
+    (
+        "presets/ko-zh-TW/translation.j2",
+        include_str!("../prompts/presets/ko-zh-TW/translation.j2"),
+    ),
+    (
+        "presets/en-zh-TW/analysis.j2",
+        include_str!("../prompts/presets/en-zh-TW/analysis.j2"),
+    ),
+    (
+        "presets/en-zh-TW/translation.j2",
+        include_str!("../prompts/presets/en-zh-TW/translation.j2"),
+    ),
+    (
+        "presets/zh-en/analysis.j2",
+        include_str!("../prompts/presets/zh-en/analysis.j2"),
+    ),
+    (
+        "presets/zh-en/translation.j2",
+        include_str!("../prompts/presets/zh-en/translation.j2"),
+    ),
+];
+
+pub const PRESETS: [&str; 5] = ["default", "ja-zh-TW", "ko-zh-TW", "en-zh-TW", "zh-en"];
+
+fn default_preset() -> String {
+    "default".to_string()
+}
+
+// prompts 區塊
+// 優先順序：inline 模板 > template_dir/<名稱>.j2 > 預設組 (preset) > 內建基本模板
+#[derive(Debug, Deserialize)]
+pub struct PromptsConfig {
+    #[serde(default = "default_preset")]
+    pub preset: String,
+    #[serde(default)]
+    pub template_dir: Option<PathBuf>,
+    #[serde(default)]
+    pub analysis_prompt: Option<String>,
+    #[serde(default)]
+    pub translation_prompt: Option<String>,
+    #[serde(default)]
+    pub story_arc_prompt: Option<String>,
+    #[serde(default)]
+    pub variables: BTreeMap<String, serde_json::Value>, // 自訂模板變數 (內建變數優先)
+}
+
+impl PromptsConfig {
+    fn inline(&self, name: &str) -> Option<&String> {
+        match name {
+            "analysis" => self.analysis_prompt.as_ref(),
+            "translation" => self.translation_prompt.as_ref(),
+            "story_arc" => self.story_arc_prompt.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+fn builtin(name: &str) -> Option<&'static str> {
+    BUILTIN_TEMPLATES
+        .iter()
+        .find(|(path, _)| *path == name)
+        .map(|(_, source)| *source)
+}
+
+// 以 / 分隔的模板名稱轉為 template_dir 內的路徑 (不允許跳出資料夾)
+fn template_path(dir: &std::path::Path, name: &str) -> Option<PathBuf> {
+    let mut path = dir.to_path_buf();
+    for segment in name.split('/') {
+        if segment.is_empty() || segment == "." || segment == ".." {
+            return None;
+        }
+        path.push(segment);
+    }
+    Some(path)
+}
+
+/// 建立模板環境：未定義的變數一律視為錯誤
+pub fn build_environment(config: &PromptsConfig) -> Result<Environment<'static>> {
+    if !PRESETS.contains(&config.preset.as_str()) {
+        bail!(
+            "未知的 prompt preset: {} (可用: {})",
+            config.preset,
+            PRESETS.join(", ")
+        );
+    }
+
+    let mut env = Environment::new();
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+
+    // include / extends 先找專案的 template_dir，再找內建模板
+    let template_dir = config.template_dir.clone();
+    env.set_loader(move |name| {
+        if let Some(path) = template_dir
+            .as_deref()
+            .and_then(|dir| template_path(dir, name))
+            .filter(|path| path.is_file())
+        {
+            return std::fs::read_to_string(&path).map(Some).map_err(|e| {
+                minijinja::Error::new(
+                    minijinja::ErrorKind::InvalidOperation,
+                    format!("無法讀取模板 {}: {}", path.display(), e),
+                )
+            });
+        }
+        Ok(builtin(name).map(str::to_string))
+    });
+
+    for name in ENTRY_TEMPLATES {
+        let source = match config.inline(name) {
+            Some(source) => source.clone(),
+            None => format!("{{% extends {:?} %}}", resolve_entry(config, name)),
+        };
+        env.add_template_owned(name, source)?;
+    }
+    Ok(env)
+}
+
+// 找出進入點模板實際使用的檔案
+fn resolve_entry(config: &PromptsConfig, name: &str) -> String {
+    let file_name = format!("{}.j2", name);
+    if let Some(dir) = &config.template_dir
+        && dir.join(&file_name).is_file()
+    {
+        return file_name;
+    }
+    let preset = format!("presets/{}/{}", config.preset, file_name);
+    if builtin(&preset).is_some() {
+        return preset;
+    }
+    format!("base/{}", file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PromptsConfig, build_environment};
+    use minijinja::context;
+
+    fn config(preset: &str) -> PromptsConfig {
+        PromptsConfig {
+            preset: preset.to_string(),
+            template_dir: None,
+            analysis_prompt: None,
+            translation_prompt: None,
+            story_arc_prompt: None,
+            variables: Default::default(),
+        }
+    }
+
+    #[test]
+    fn every_builtin_preset_builds() {
+        for preset in super::PRESETS {
+            assert!(build_environment(&config(preset)).is_ok(), "{}", preset);
+        }
+    }
+
+    #[test]
+    fn unknown_preset_is_rejected() {
+        assert!(build_environment(&config("xx-yy")).is_err());
+    }
+
+    #[test]
+    fn preset_without_template_falls_back_to_base() {
+        let env = build_environment(&config("zh-en")).unwrap();
+        let rendered = env
+            .get_template("story_arc")
+            .unwrap()
+            .render(context! { summary_len => 100 })
+            .unwrap();
+        assert!(rendered.contains("100"));
+    }
+
+    #[test]
+    fn template_dir_overrides_preset_and_can_include_builtins() {
+        let dir = std::env::temp_dir().join(format!(
+            "ai-novel-translation-prompts-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("story_arc.j2"),
+            "{% include \"base/story_arc.j2\" %}{% include \"notes.j2\" %}",
+        )
+        .unwrap();
+        std::fs::write(dir.join("notes.j2"), "自訂說明").unwrap();
+
+        let mut config = config("ja-zh-TW");
+        config.template_dir = Some(dir.clone());
+        let env = build_environment(&config).unwrap();
+        let rendered = env
+            .get_template("story_arc")
+            .unwrap()
+            .render(context! { summary_len => 100 })
+            .unwrap();
+        assert!(rendered.contains("100"));
+        assert!(rendered.ends_with("自訂說明"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn inline_prompt_overrides_preset_and_rejects_undefined_variables() {
+        let mut config = config("ja-zh-TW");
+        config.story_arc_prompt = Some("{{ missing_variable }}".to_string());
+        let env = build_environment(&config).unwrap();
+        let result = env.get_template("story_arc").unwrap().render(context! {});
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,149 @@
+// src/replay.rs
+
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::audit::content_hash;
+use crate::llm::{LlmClient, LlmResponse, ResponseFormat, TokenUsage};
+
+// 稽核紀錄中重播需要的欄位
+#[derive(Debug, Deserialize)]
+struct RecordedCall {
+    system_prompt: String,
+    content_hash: String,
+    format: String,
+    response: Option<String>,
+    usage: Option<TokenUsage>,
+}
+
+type RequestKey = (String, String, String); // (system prompt, 內文雜湊, 回應格式)
+
+/// 以稽核紀錄 (audit_log) 取代 LLM：相同的 prompt、內文與格式回傳當時的回應
+/// 同一請求出現多次時 (例如重試) 依記錄順序回傳，用完後重複最後一個
+pub struct ReplayClient {
+    responses: Mutex<HashMap<RequestKey, VecDeque<(String, TokenUsage)>>>,
+}
+
+impl ReplayClient {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs_err::read_to_string(path)?;
+        let mut responses: HashMap<RequestKey, VecDeque<(String, TokenUsage)>> = HashMap::new();
+        for (line_no, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let call: RecordedCall = serde_json::from_str(line)
+                .with_context(|| format!("無法解析稽核紀錄第 {} 行", line_no + 1))?;
+            // 失敗的呼叫沒有回應可以重播
+            let Some(response) = call.response else {
+                continue;
+            };
+            responses
+                .entry((call.system_prompt, call.content_hash, call.format))
+                .or_default()
+                .push_back((response, call.usage.unwrap_or_default()));
+        }
+        if responses.is_empty() {
+            bail!("稽核紀錄中沒有可重播的回應: {}", path.display());
+        }
+        Ok(Self {
+            responses: Mutex::new(responses),
+        })
+    }
+}
+
+#[async_trait]
+impl LlmClient for ReplayClient {
+    async fn generate(
+        &self,
+        system_prompt: &str,
+        user_content: &str,
+        format: &ResponseFormat,
+    ) -> Result<LlmResponse> {
+        let key = (
+            system_prompt.to_string(),
+            content_hash(user_content),
+            format.describe(),
+        );
+        let mut responses = self.responses.lock().unwrap();
+        let Some(queue) = responses.get_mut(&key) else {
+            bail!("稽核紀錄中沒有相同的請求 (prompt 或內文已變更)");
+        };
+        let (text, usage) = if queue.len() > 1 {
+            queue.pop_front().unwrap()
+        } else {
+            queue.front().cloned().unwrap()
+        };
+        Ok(LlmResponse {
+            text,
+            usage,
+            cached: true,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReplayClient;
+    use crate::audit::{AuditRecord, content_hash};
+    use crate::llm::{LlmClient, ResponseFormat};
+
+    fn record<'a>(prompt: &'a str, content: &'a str, response: Option<&'a str>) -> String {
+        serde_json::to_string(&AuditRecord {
+            timestamp: 0,
+            chapter: "001",
+            pass: "translation",
+            provider: "ollama",
+            model: "m",
+            format: ResponseFormat::Text.describe(),
+            system_prompt: prompt,
+            content,
+            content_hash: content_hash(content),
+            content_chars: content.chars().count(),
+            response,
+            cached: false,
+            latency_ms: 1,
+            usage: None,
+            error: response.is_none().then(|| "timeout".to_string()),
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn recorded_run_is_replayed_in_order() {
+        let path = std::env::temp_dir().join(format!(
+            "ai-novel-translation-replay-{}.jsonl",
+            std::process::id()
+        ));
+        let log = [
+            record("翻譯", "原文", None),
+            record("翻譯", "原文", Some("譯文一")),
+            record("翻譯", "原文", Some("譯文二")),
+            record("分析", "原文", Some("{}")),
+        ]
+        .join("\n");
+        std::fs::write(&path, log).unwrap();
+
+        let client = ReplayClient::load(&path).unwrap();
+        let text = &ResponseFormat::Text;
+        assert_eq!(
+            client.generate("翻譯", "原文", text).await.unwrap().text,
+            "譯文一"
+        );
+        assert_eq!(
+            client.generate("翻譯", "原文", text).await.unwrap().text,
+            "譯文二"
+        );
+        assert_eq!(
+            client.generate("翻譯", "原文", text).await.unwrap().text,
+            "譯文二"
+        );
+        assert!(client.generate("分析", "原文", text).await.unwrap().cached);
+        assert!(client.generate("翻譯", "改過的原文", text).await.is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,254 @@
+// src/ruby.rs
+
+use regex::{Captures, Regex};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::LazyLock;
+
+use crate::bilingual::escape_html;
+
+/// 原文中的讀音標註 (ruby)：base 為本文，reading 為讀音
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Reading {
+    pub base: String,
+    pub reading: String,
+}
+
+// HTML 的 <ruby>本文<rp>(</rp><rt>讀音</rt><rp>)</rp></ruby>
+static HTML_RUBY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)<ruby\b[^>]*>(.*?)</ruby\s*>").unwrap());
+static HTML_RT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)<rt\b[^>]*>(.*?)(?:</rt\s*>|$)").unwrap());
+static HTML_RP: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)<rp\b[^>]*>.*?</rp\s*>").unwrap());
+static HTML_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]*>").unwrap());
+
+// 全形｜本文《讀音》：不會出現在一般文章中，任何語言都處理
+static FULL_WIDTH_RUBY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"｜([^｜|《》\n]+?)《([^《》\n]+)》").unwrap());
+// 青空文庫/小説家になろう的其他寫法：|本文《讀音》、漢字《讀音》、｜本文(讀音)、漢字（かな）
+// 中文的《書名》、一般文章的 | 與括號也會符合，只用於日文原文
+static JAPANESE_RUBY: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(concat!(
+        r"[｜|]([^｜|《》\n]+?)《([^《》\n]+)》",
+        r"|([\p{Han}々〆ヶ]+)《([^《》\n]+)》",
+        r"|[｜|]([^｜|（）()《》\n]+?)[（(]([^（）()\n]+)[）)]",
+        r"|([\p{Han}々〆ヶ]+)[（(]([\p{Hiragana}\p{Katakana}ー]{1,20})[）)]",
+    ))
+    .unwrap()
+});
+// ｜《 為不是標註的《
+static ESCAPED_BRACKET: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"｜([《(（])").unwrap());
+static JAPANESE_ESCAPED_BRACKET: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"[｜|]([《(（])").unwrap());
+// 青空文庫的注記 ［＃…］
+static AOZORA_NOTE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"［＃[^］\n]*］").unwrap());
+
+// 傍点 (｜強調《・・・》) 不是讀音
+fn is_emphasis(reading: &str) -> bool {
+    reading.chars().all(|c| "・﹅﹆●○◦•".contains(c))
+}
+
+fn record(readings: &mut Vec<Reading>, base: &str, reading: &str) {
+    let (base, reading) = (base.trim(), reading.trim());
+    if base.is_empty() || reading.is_empty() || is_emphasis(reading) {
+        return;
+    }
+    if !readings.iter().any(|r| r.base == base) {
+        readings.push(Reading {
+            base: base.to_string(),
+            reading: reading.to_string(),
+        });
+    }
+}
+
+/// 移除 ruby 標記與青空文庫注記，只留下本文；回傳 (文字, 讀音，依出現順序且同一本文只取第一個)
+/// HTML <ruby> 與全形｜本文《讀音》一律處理，其他文字寫法只在 japanese 為 true 時處理
+pub fn strip(text: &str, japanese: bool) -> (String, Vec<Reading>) {
+    let mut readings = Vec::new();
+    let text = HTML_RUBY.replace_all(text, |caps: &Captures| {
+        let inner = HTML_RP.replace_all(&caps[1], "");
+        let reading: String = HTML_RT
+            .captures_iter(&inner)
+            .map(|rt| HTML_TAG.replace_all(&rt[1], "").into_owned())
+            .collect();
+        let base = HTML_TAG
+            .replace_all(&HTML_RT.replace_all(&inner, ""), "")
+            .into_owned();
+        record(&mut readings, &base, &reading);
+        base
+    });
+    let (text_ruby, escaped_bracket) = if japanese {
+        (&*JAPANESE_RUBY, &*JAPANESE_ESCAPED_BRACKET)
+    } else {
+        (&*FULL_WIDTH_RUBY, &*ESCAPED_BRACKET)
+    };
+    let text = text_ruby.replace_all(&text, |caps: &Captures| {
+        let groups = [(1, 2), (3, 4), (5, 6), (7, 8)];
+        let (base, reading) = groups
+            .iter()
+            .find_map(|(b, r)| Some((caps.get(*b)?.as_str(), caps.get(*r)?.as_str())))
+            .unwrap();
+        record(&mut readings, base, reading);
+        base.to_string()
+    });
+    let text = escaped_bracket.replace_all(&text, "$1");
+    let text = AOZORA_NOTE.replace_all(&text, "");
+    (text.into_owned(), readings)
+}
+
+/// 提供給 prompt 的讀音：本章的標註，加上先前章節標註過、且在本章出現的詞
+pub fn relevant(known: &BTreeMap<String, String>, found: &[Reading], text: &str) -> Vec<Reading> {
+    let mut readings = found.to_vec();
+    for (base, reading) in known {
+        if text.contains(base.as_str()) && !readings.iter().any(|r| &r.base == base) {
+            readings.push(Reading {
+                base: base.clone(),
+                reading: reading.clone(),
+            });
+        }
+    }
+    readings
+}
+
+/// 譯註：本章標註過讀音的詞，附上譯名 (名詞表中有時)、原文與讀音
+pub fn notes(found: &[Reading], terms: &HashMap<String, String>) -> Vec<String> {
+    found
+        .iter()
+        .map(|r| match terms.get(&r.base) {
+            Some(term) => format!("{}：{}（{}）", term, r.base, r.reading),
+            None => format!("{}（{}）", r.base, r.reading),
+        })
+        .collect()
+}
+
+/// 譯文中對應的讀音標註：本文換成譯名 (名詞表中有時)，供 HTML 輸出加回 <ruby>
+pub fn translated(found: &[Reading], terms: &HashMap<String, String>) -> Vec<Reading> {
+    found
+        .iter()
+        .map(|r| Reading {
+            base: terms.get(&r.base).unwrap_or(&r.base).clone(),
+            reading: r.reading.clone(),
+        })
+        .collect()
+}
+
+// at 是否位於 open 與 close 之間 (例如標籤內或既有的 <ruby> 內)
+fn inside(text: &str, at: usize, open: &str, close: &str) -> bool {
+    text[..at]
+        .rfind(open)
+        .is_some_and(|start| !text[start..at].contains(close))
+}
+
+/// 在 HTML 譯文中加回讀音：每個詞只標註第一次出現處，略過標籤內與已有 <ruby> 的文字
+pub fn annotate_html(text: &str, readings: &[Reading]) -> String {
+    let mut text = text.to_string();
+    for r in readings {
+        let position = text
+            .match_indices(r.base.as_str())
+            .map(|(at, _)| at)
+            .find(|&at| !inside(&text, at, "<", ">") && !inside(&text, at, "<ruby", "</ruby>"));
+        if let Some(at) = position {
+            let end = at + r.base.len();
+            text = format!(
+                "{}<ruby>{}<rt>{}</rt></ruby>{}",
+                &text[..at],
+                &text[at..end],
+                escape_html(&r.reading),
+                &text[end..]
+            );
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Reading, annotate_html, relevant, strip, translated};
+    use std::collections::{BTreeMap, HashMap};
+
+    fn reading(base: &str, reading: &str) -> Reading {
+        Reading {
+            base: base.to_string(),
+            reading: reading.to_string(),
+        }
+    }
+
+    #[test]
+    fn aozora_and_narou_ruby_are_stripped() {
+        let (text, readings) = strip(
+            "｜四月一日《わたぬき》君は魔導書《グリモワール》を開き、\
+             ｜強調《・・》して|Alice(アリス)と呼んだ。［＃改ページ］東京（とうきょう）｜《括弧》",
+            true,
+        );
+        assert_eq!(
+            text,
+            "四月一日君は魔導書を開き、強調してAliceと呼んだ。東京《括弧》"
+        );
+        assert_eq!(
+            readings,
+            [
+                reading("四月一日", "わたぬき"),
+                reading("魔導書", "グリモワール"),
+                reading("Alice", "アリス"),
+                reading("東京", "とうきょう"),
+            ]
+        );
+    }
+
+    #[test]
+    fn html_ruby_is_stripped() {
+        let (text, readings) = strip(
+            "彼は<ruby>剣<rp>(</rp><rt>つるぎ</rt><rp>)</rp></ruby>と\
+             <ruby><rb>漢</rb><rt>かん</rt>字<rt>じ</rt></ruby>を<em>見た</em>。",
+            false,
+        );
+        assert_eq!(text, "彼は剣と漢字を<em>見た</em>。");
+        assert_eq!(
+            readings,
+            [reading("剣", "つるぎ"), reading("漢字", "かんじ")]
+        );
+    }
+
+    #[test]
+    fn loose_ruby_forms_are_only_used_for_japanese() {
+        let prose = "他讀了《紅樓夢》，說 a|b(c) 與王(おう)不同，｜魔王《まおう》來了。";
+        let (text, readings) = strip(prose, false);
+        assert_eq!(
+            text,
+            "他讀了《紅樓夢》，說 a|b(c) 與王(おう)不同，魔王來了。"
+        );
+        assert_eq!(readings, [reading("魔王", "まおう")]);
+    }
+
+    #[test]
+    fn earlier_readings_are_kept_when_the_word_appears() {
+        let known = BTreeMap::from([
+            ("四月一日".to_string(), "わたぬき".to_string()),
+            ("魔王".to_string(), "まおう".to_string()),
+        ]);
+        let found = [reading("剣", "つるぎ")];
+        let readings = relevant(&known, &found, "四月一日は剣を抜いた");
+        assert_eq!(
+            readings,
+            [reading("剣", "つるぎ"), reading("四月一日", "わたぬき")]
+        );
+    }
+
+    #[test]
+    fn ruby_is_restored_on_the_translated_word() {
+        let terms = HashMap::from([("剣".to_string(), "劍".to_string())]);
+        let readings = translated(
+            &[reading("剣", "つるぎ"), reading("魔王", "まおう")],
+            &terms,
+        );
+        assert_eq!(readings[0], reading("劍", "つるぎ"));
+        assert_eq!(
+            annotate_html(
+                "<a title=\"劍\">劍</a>與劍，<ruby>魔王<rt>x</rt></ruby>",
+                &readings
+            ),
+            "<a title=\"劍\"><ruby>劍<rt>つるぎ</rt></ruby></a>與劍，<ruby>魔王<rt>x</rt></ruby>"
+        );
+    }
+}
@@ -0,0 +1,71 @@
+// src/session.rs
+
+use anyhow::Result;
+use std::time::Instant;
+
+use crate::audit::{AuditLog, AuditRecord, content_hash};
+use crate::llm::{LlmClient, LlmResponse, ResponseFormat};
+use crate::usage::{UsageTracker, unix_now};
+
+/// 一次執行中所有 LLM 呼叫的入口：統一記錄用量與稽核紀錄
+pub struct LlmSession {
+    client: Box<dyn LlmClient>,
+    pub usage: UsageTracker,
+    audit: Option<AuditLog>,
+}
+
+impl LlmSession {
+    pub fn new(client: Box<dyn LlmClient>, usage: UsageTracker, audit: Option<AuditLog>) -> Self {
+        Self {
+            client,
+            usage,
+            audit,
+        }
+    }
+
+    pub async fn generate(
+        &mut self,
+        chapter: &str,
+        pass: &str,
+        system_prompt: &str,
+        user_content: &str,
+        format: &ResponseFormat,
+    ) -> Result<LlmResponse> {
+        let started = Instant::now();
+        let result = self
+            .client
+            .generate(system_prompt, user_content, format)
+            .await;
+
+        if let Ok(resp) = &result {
+            self.usage.record_response(chapter, pass, resp);
+        }
+
+        if let Some(audit) = &self.audit {
+            let manifest = self.usage.manifest();
+            let record = AuditRecord {
+                timestamp: unix_now(),
+                chapter,
+                pass,
+                provider: &manifest.provider,
+                model: &manifest.model,
+                format: format.describe(),
+                system_prompt,
+                content: user_content,
+                content_hash: content_hash(user_content),
+                content_chars: user_content.chars().count(),
+                response: result.as_ref().ok().map(|r| r.text.as_str()),
+                cached: result.as_ref().is_ok_and(|r| r.cached),
+                latency_ms: started.elapsed().as_millis(),
+                usage: result.as_ref().ok().map(|r| r.usage),
+                error: result.as_ref().err().map(|e| format!("{:#}", e)),
+            };
+            // 稽核紀錄寫入失敗不影響翻譯流程
+            if let Err(e) = audit.write(&record) {
+                eprintln!("[警告] 寫入稽核紀錄失敗: {:?}", e);
+            }
+        }
+
+        result
+    }
+}
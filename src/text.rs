@@ -0,0 +1,32 @@
+// src/text.rs
+
+/// 以非空白行作為段落 (網路小說常見一行一段，中間夾雜空行)
+pub fn paragraphs(text: &str) -> Vec<&str> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+/// 取最後 count 個段落，以換行連接
+pub fn tail_paragraphs(text: &str, count: usize) -> String {
+    let all = paragraphs(text);
+    all[all.len().saturating_sub(count)..].join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{paragraphs, tail_paragraphs};
+
+    #[test]
+    fn blank_lines_are_not_paragraphs() {
+        assert_eq!(paragraphs("一\n\n  二  \n\n\n三\n"), vec!["一", "二", "三"]);
+    }
+
+    #[test]
+    fn tail_returns_last_paragraphs() {
+        assert_eq!(tail_paragraphs("一\n\n二\n三", 2), "二\n三");
+        assert_eq!(tail_paragraphs("一", 5), "一");
+        assert_eq!(tail_paragraphs("一\n二", 0), "");
+    }
+}
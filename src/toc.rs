@@ -0,0 +1,226 @@
+// src/toc.rs
+
+use anyhow::Result;
+use serde::Serialize;
+
+/// 目錄檔格式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TocFormat {
+    Markdown,
+    Json,
+    Csv,
+}
+
+impl TocFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "md" | "markdown" => Some(Self::Markdown),
+            "json" => Some(Self::Json),
+            "csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Json => "json",
+            Self::Csv => "csv",
+        }
+    }
+}
+
+/// 章節進度：pending 尚未分析、analyzed 已有字典但譯文不齊、translated 全部語言都已輸出
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChapterStatus {
+    Pending,
+    Analyzed,
+    Translated,
+}
+
+impl ChapterStatus {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Analyzed => "analyzed",
+            Self::Translated => "translated",
+        }
+    }
+}
+
+/// 某個目標語言的譯名與字數；尚未翻譯時 title 為空、words 為 0
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct TocTranslation {
+    pub language: String,
+    pub title: String,
+    pub words: usize,
+}
+
+/// 目錄的一列
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct TocEntry {
+    pub index: usize, // 從 1 開始
+    pub file: String,
+    pub source_title: String,
+    pub source_words: usize,
+    pub translations: Vec<TocTranslation>,
+    pub status: ChapterStatus,
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30ff}' // 平假名、片假名
+        | '\u{3400}'..='\u{4dbf}'
+        | '\u{4e00}'..='\u{9fff}'
+        | '\u{f900}'..='\u{faff}'
+        | '\u{ac00}'..='\u{d7af}' // 韓文
+        | '\u{20000}'..='\u{2ffff}')
+}
+
+/// 字數：中日韓文字每字算一個，其他語言以連續的字母或數字算一個詞
+pub fn word_count(text: &str) -> usize {
+    let mut count = 0;
+    let mut in_word = false;
+    for c in text.chars() {
+        if is_cjk(c) {
+            count += 1;
+            in_word = false;
+        } else if c.is_alphanumeric() {
+            if !in_word {
+                count += 1;
+            }
+            in_word = true;
+        } else if !matches!(c, '\'' | '’' | '-') {
+            in_word = false;
+        }
+    }
+    count
+}
+
+// 表頭：只有一個目標語言時不標示語言
+fn headers(languages: &[&str]) -> Vec<String> {
+    let mut headers: Vec<String> = ["index", "file", "source_title", "source_words"]
+        .map(String::from)
+        .to_vec();
+    for language in languages {
+        if languages.len() == 1 {
+            headers.push("translated_title".to_string());
+            headers.push("translated_words".to_string());
+        } else {
+            headers.push(format!("translated_title ({})", language));
+            headers.push(format!("translated_words ({})", language));
+        }
+    }
+    headers.push("status".to_string());
+    headers
+}
+
+fn cells(entry: &TocEntry) -> Vec<String> {
+    let mut cells = vec![
+        entry.index.to_string(),
+        entry.file.clone(),
+        entry.source_title.clone(),
+        entry.source_words.to_string(),
+    ];
+    for translation in &entry.translations {
+        cells.push(translation.title.clone());
+        cells.push(translation.words.to_string());
+    }
+    cells.push(entry.status.name().to_string());
+    cells
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn markdown_cell(cell: &str) -> String {
+    cell.replace('|', "\\|").replace(['\r', '\n'], " ")
+}
+
+/// 依格式輸出目錄；languages 為目標語言，順序與每列的 translations 相同
+pub fn render(format: TocFormat, entries: &[TocEntry], languages: &[&str]) -> Result<String> {
+    let rows = std::iter::once(headers(languages)).chain(entries.iter().map(cells));
+    let text = match format {
+        TocFormat::Json => serde_json::to_string_pretty(entries)? + "\n",
+        TocFormat::Csv => rows
+            .map(|row| {
+                let fields: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+                fields.join(",") + "\n"
+            })
+            .collect(),
+        TocFormat::Markdown => {
+            let columns = headers(languages).len();
+            let mut lines: Vec<String> = rows
+                .map(|row| {
+                    let cells: Vec<String> = row.iter().map(|cell| markdown_cell(cell)).collect();
+                    format!("| {} |", cells.join(" | "))
+                })
+                .collect();
+            lines.insert(1, format!("|{}", " --- |".repeat(columns)));
+            lines.join("\n") + "\n"
+        }
+    };
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChapterStatus, TocEntry, TocFormat, TocTranslation, render, word_count};
+
+    fn entry() -> TocEntry {
+        TocEntry {
+            index: 1,
+            file: "001.txt".to_string(),
+            source_title: "第一話|旅立ち".to_string(),
+            source_words: 1200,
+            translations: vec![TocTranslation {
+                language: "English".to_string(),
+                title: "Chapter 1, \"Departure\"".to_string(),
+                words: 800,
+            }],
+            status: ChapterStatus::Translated,
+        }
+    }
+
+    #[test]
+    fn cjk_characters_and_latin_words_are_counted() {
+        assert_eq!(word_count("第一章　出發"), 5);
+        assert_eq!(word_count("It's a well-known fact, 2 times."), 6);
+        assert_eq!(word_count("魔王 Alice と話す"), 6);
+    }
+
+    #[test]
+    fn toc_is_rendered_in_each_format() {
+        let entries = [entry()];
+        let markdown = render(TocFormat::Markdown, &entries, &["English"]).unwrap();
+        assert_eq!(
+            markdown,
+            "| index | file | source_title | source_words | translated_title | translated_words | status |\n\
+             | --- | --- | --- | --- | --- | --- | --- |\n\
+             | 1 | 001.txt | 第一話\\|旅立ち | 1200 | Chapter 1, \"Departure\" | 800 | translated |\n"
+        );
+        let csv = render(TocFormat::Csv, &entries, &["English", "Korean"]).unwrap();
+        assert_eq!(
+            csv.lines().next().unwrap(),
+            "index,file,source_title,source_words,translated_title (English),translated_words (English),translated_title (Korean),translated_words (Korean),status"
+        );
+        assert!(csv.ends_with(
+            "1,001.txt,第一話|旅立ち,1200,\"Chapter 1, \"\"Departure\"\"\",800,translated\n"
+        ));
+        let json: serde_json::Value =
+            serde_json::from_str(&render(TocFormat::Json, &entries, &["English"]).unwrap())
+                .unwrap();
+        assert_eq!(
+            json[0]["translations"][0]["title"],
+            "Chapter 1, \"Departure\""
+        );
+        assert_eq!(json[0]["status"], "translated");
+        assert_eq!(TocFormat::parse("MD"), Some(TocFormat::Markdown));
+    }
+}
@@ -0,0 +1,209 @@
+// src/usage.rs
+
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::llm::{LlmResponse, ModelPricing, TokenUsage};
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// 單一 pass (分析 / 翻譯) 的累計
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct PassUsage {
+    pub requests: u64,
+    pub usage: TokenUsage,
+}
+
+// 單一章節的累計
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct ChapterUsage {
+    pub chapter: String,
+    pub requests: u64,
+    pub cache_hits: u64,
+    pub usage: TokenUsage,
+    pub estimated_cost: Option<f64>,
+    pub passes: BTreeMap<String, PassUsage>,
+}
+
+// 整次執行的紀錄，存放於 glossary_folder/runs/
+#[derive(Debug, Serialize, Clone)]
+pub struct RunManifest {
+    pub provider: String,
+    pub model: String,
+    pub started_at: u64,
+    pub finished_at: Option<u64>,
+    pub requests: u64,
+    pub cache_hits: u64,
+    pub usage: TokenUsage,
+    pub estimated_cost: Option<f64>,
+    pub chapters: Vec<ChapterUsage>,
+}
+
+pub struct UsageTracker {
+    pricing: Option<ModelPricing>,
+    manifest: RunManifest,
+}
+
+impl UsageTracker {
+    pub fn new(provider: &str, model: &str, pricing: Option<ModelPricing>) -> Self {
+        Self {
+            pricing,
+            manifest: RunManifest {
+                provider: provider.to_string(),
+                model: model.to_string(),
+                started_at: unix_now(),
+                finished_at: None,
+                requests: 0,
+                cache_hits: 0,
+                usage: TokenUsage::default(),
+                estimated_cost: pricing.map(|_| 0.0),
+                chapters: Vec::new(),
+            },
+        }
+    }
+
+    /// 記錄一次回應；快取命中不計入請求數與用量
+    pub fn record_response(&mut self, chapter: &str, pass: &str, resp: &LlmResponse) {
+        if resp.cached {
+            self.manifest.cache_hits += 1;
+            self.chapter_entry(chapter).cache_hits += 1;
+        } else {
+            self.record(chapter, pass, resp.usage);
+        }
+    }
+
+    /// 記錄一次 LLM 呼叫的用量
+    pub fn record(&mut self, chapter: &str, pass: &str, usage: TokenUsage) {
+        let pricing = self.pricing;
+        let manifest = &mut self.manifest;
+
+        manifest.requests += 1;
+        manifest.usage += usage;
+        manifest.estimated_cost = pricing.map(|p| p.cost(&manifest.usage));
+
+        let entry = self.chapter_entry(chapter);
+        entry.requests += 1;
+        entry.usage += usage;
+        entry.estimated_cost = pricing.map(|p| p.cost(&entry.usage));
+
+        let pass_entry = entry.passes.entry(pass.to_string()).or_default();
+        pass_entry.requests += 1;
+        pass_entry.usage += usage;
+    }
+
+    fn chapter_entry(&mut self, chapter: &str) -> &mut ChapterUsage {
+        let chapters = &mut self.manifest.chapters;
+        let index = match chapters.iter().position(|c| c.chapter == chapter) {
+            Some(index) => index,
+            None => {
+                chapters.push(ChapterUsage {
+                    chapter: chapter.to_string(),
+                    ..Default::default()
+                });
+                chapters.len() - 1
+            }
+        };
+        &mut chapters[index]
+    }
+
+    pub fn chapter(&self, chapter: &str) -> Option<&ChapterUsage> {
+        self.manifest.chapters.iter().find(|c| c.chapter == chapter)
+    }
+
+    pub fn manifest(&self) -> &RunManifest {
+        &self.manifest
+    }
+
+    pub fn finish(&mut self) {
+        self.manifest.finished_at = Some(unix_now());
+    }
+
+    /// 寫入 runs/run-<開始時間>.json，回傳檔案路徑
+    pub fn save(&self, glossary_folder: &Path) -> Result<PathBuf> {
+        let folder = glossary_folder.join("runs");
+        fs_err::create_dir_all(&folder)?;
+        let path = folder.join(format!("run-{}.json", self.manifest.started_at));
+        fs_err::write(&path, serde_json::to_string_pretty(&self.manifest)?)?;
+        Ok(path)
+    }
+}
+
+pub fn format_usage(usage: &TokenUsage, cost: Option<f64>) -> String {
+    let mut text = format!(
+        "輸入 {} / 輸出 {} tokens (合計 {})",
+        usage.prompt_tokens,
+        usage.completion_tokens,
+        usage.total()
+    );
+    if let Some(cost) = cost {
+        text.push_str(&format!(" (估計費用 ${:.4})", cost));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UsageTracker;
+    use crate::llm::{LlmResponse, ModelPricing, TokenUsage};
+
+    fn usage(prompt: u64, completion: u64) -> TokenUsage {
+        TokenUsage {
+            prompt_tokens: prompt,
+            completion_tokens: completion,
+        }
+    }
+
+    #[test]
+    fn tracker_accumulates_per_pass_chapter_and_run() {
+        let mut tracker = UsageTracker::new("openai", "gpt-4o", None);
+        tracker.record("001", "analysis", usage(100, 10));
+        tracker.record("001", "translation", usage(200, 150));
+        tracker.record("002", "analysis", usage(50, 5));
+
+        let chapter = tracker.chapter("001").unwrap();
+        assert_eq!(chapter.requests, 2);
+        assert_eq!(chapter.usage, usage(300, 160));
+        assert_eq!(chapter.passes["translation"].usage, usage(200, 150));
+
+        let manifest = tracker.manifest();
+        assert_eq!(manifest.requests, 3);
+        assert_eq!(manifest.usage.total(), 515);
+        assert_eq!(manifest.estimated_cost, None);
+    }
+
+    #[test]
+    fn cache_hits_are_not_counted_as_requests() {
+        let mut tracker = UsageTracker::new("openai", "gpt-4o", None);
+        let resp = LlmResponse {
+            text: String::new(),
+            usage: TokenUsage::default(),
+            cached: true,
+        };
+        tracker.record_response("001", "analysis", &resp);
+
+        assert_eq!(tracker.manifest().requests, 0);
+        assert_eq!(tracker.manifest().cache_hits, 1);
+        assert_eq!(tracker.chapter("001").unwrap().cache_hits, 1);
+    }
+
+    #[test]
+    fn tracker_estimates_cost_with_pricing() {
+        let pricing = ModelPricing {
+            input_per_million: 2.0,
+            output_per_million: 10.0,
+        };
+        let mut tracker = UsageTracker::new("openai", "gpt-4o", Some(pricing));
+        tracker.record("001", "analysis", usage(1_000_000, 100_000));
+
+        let cost = tracker.manifest().estimated_cost.unwrap();
+        assert!((cost - 3.0).abs() < 1e-9);
+    }
+}